@@ -3,7 +3,10 @@ use crate::lib::kinput::*;
 use crate::lib::kimg::*;
 use crate::krenderer::*;
 use crate::game::*;
-use glutin::event::{Event, WindowEvent};
+use crate::replay::*;
+use crate::profile::*;
+use crate::sprites::*;
+use glutin::event::{Event, WindowEvent, VirtualKeyCode};
 
 pub struct Application {
     gl: glow::Context,
@@ -16,6 +19,7 @@ pub struct Application {
     pub yres: f32,
 
     game: Game,
+    recorder: Option<ReplayWriter>,
 }
 
 pub fn load_file(paths: &[&str]) -> String {
@@ -28,7 +32,7 @@ pub fn load_file(paths: &[&str]) -> String {
 }
 
 impl Application {
-    pub fn new(event_loop: &glutin::event_loop::EventLoop<()>) -> Application {
+    pub fn new(event_loop: &glutin::event_loop::EventLoop<()>, record_path: Option<String>) -> Application {
         let default_xres = 1600.0;
         let default_yres = 1600.0;
 
@@ -53,6 +57,15 @@ impl Application {
             .expect("couldn't load atlas from ./atlas.png");
 
         let renderer = KRenderer::new(&gl, uv_shader, atlas);
+        let sprites = SpriteSheet::from_json(&load_file(&["src/atlas.json", "../../src/atlas.json", "atlas.json"]))
+            .expect("couldn't parse atlas.json");
+
+        // if its broken or from a newer build play without one rather than write over it
+        let profile = load_profile(PROFILE_PATH).map_err(|e| println!("not using profile: {}", e)).ok();
+        let recorder = record_path.map(|path| ReplayWriter::new(&path, profile.as_ref()).expect("couldn't create replay file"));
+        let profile_path = profile.as_ref().map(|_| PROFILE_PATH.to_string());
+        let mut game = Game::new(profile, profile_path);
+        game.sprites = sprites;
 
         Application {
             gl,
@@ -60,7 +73,8 @@ impl Application {
             renderer,
             event_aggregator: EventAggregator::new(default_xres, default_yres),
 
-            game: Game::new(),
+            game,
+            recorder,
 
             xres: default_xres,
             yres: default_yres,
@@ -85,11 +99,26 @@ impl Application {
                 _ => {},
             _ => {},
             }
+            Event::LoopDestroyed => {
+                if let Some(recorder) = self.recorder.as_mut() {
+                    recorder.flush().expect("couldn't write replay");
+                }
+            },
             _ => {},
         }
 
-        if let Some(inputs) = self.event_aggregator.handle_event(event) {
-            
+        if let Some(mut inputs) = self.event_aggregator.handle_event(event) {
+            // no time passes while unfocused, this way the replay sees it too
+            if self.game.paused {
+                inputs.dt = 0.0;
+            }
+            if let Some(recorder) = self.recorder.as_mut() {
+                if inputs.just_pressed(VirtualKeyCode::F9) {
+                    println!("warning: quickloaded while recording, the replay wont play back");
+                }
+                recorder.record(&inputs).expect("couldn't write replay");
+            }
+
             unsafe { self.gl.clear(glow::COLOR_BUFFER_BIT | glow::DEPTH_BUFFER_BIT); } 
 
             let mut kc = KRCanvas::new(inputs.screen_rect);
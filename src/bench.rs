@@ -0,0 +1,32 @@
+use std::f32::consts::PI;
+use std::time::Instant;
+
+use crate::lib::kmath::*;
+use crate::simulation::*;
+
+// Step a crowd of enemies around the player and time it, to check the collision queries scale.
+// Run with --bench, in release or the numbers are meaningless
+pub fn run_benchmark() {
+    let ticks = 120;
+    for n in [400, 1000, 2000, 5000, 10000] {
+        let mut sim = Simulation::new(1234);
+        let mut seed = 1234;
+        for _ in 0..n {
+            // uniform over the disc enemies live in
+            let r = 4.0 * krand(seed).sqrt();
+            let theta = kuniform(khash(seed), 0.0, 2.0 * PI);
+            sim.spawn_enemy(Vec2::new(0.0, 0.0).offset_r_theta(r, theta), pick_enemy_kind(khash(khash(seed)), false));
+            seed = khash(khash(seed));
+        }
+
+        let mut commands = PlayerCommands::idle();
+        commands.attack = true;
+        let start = Instant::now();
+        for i in 0..ticks {
+            commands.aim = sim.player_pos + Vec2::new(1.0, 0.0).rotate(i as f32);
+            sim.step(&commands, SIM_DT);
+        }
+        let ms_per_tick = start.elapsed().as_secs_f64() * 1000.0 / ticks as f64;
+        println!("{:6} enemies: {:8.3} ms/tick ({} left)", n, ms_per_tick, sim.enemy_pos.len());
+    }
+}
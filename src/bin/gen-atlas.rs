@@ -0,0 +1,302 @@
+// Draws the bits of the atlas that are made in code rather than in atlas.xcf, so they can be
+// edited as text. Rerun this after touching either:
+//  cargo run --bin gen-atlas -- [atlas.png]
+// The font is ascii ' ' to '~' in a row of GLYPH_W x GLYPH_H cells from FONT_ORIGIN, which is where
+// krenderer looks for it. Glyphs are 5x7 with the spare column and row left clear as spacing.
+// Then flat placeholder sprites for the things the game looks up by name, until theres real art,
+// and placeholder animations made out of them. Their cells are in atlas.json too.
+
+#[allow(dead_code)]
+#[path = "../lib/kimg.rs"]
+mod kimg;
+
+use kimg::*;
+
+const ATLAS_CELL: usize = 32;
+const FONT_ORIGIN: (usize, usize) = (0, 512);
+const GLYPH_W: usize = 6;
+const GLYPH_H: usize = 8;
+
+// rows top to bottom, # is set
+const GLYPHS: [[&str; 7]; 95] = [
+    [".....", ".....", ".....", ".....", ".....", ".....", "....."], // space
+    ["..#..", "..#..", "..#..", "..#..", "..#..", ".....", "..#.."], // !
+    [".#.#.", ".#.#.", ".....", ".....", ".....", ".....", "....."], // "
+    [".#.#.", ".#.#.", "#####", ".#.#.", "#####", ".#.#.", ".#.#."], // #
+    ["..#..", ".####", "#.#..", ".###.", "..#.#", "####.", "..#.."], // $
+    ["##...", "##..#", "...#.", "..#..", ".#...", "#..##", "...##"], // %
+    [".##..", "#..#.", "#.#..", ".#...", "#.#.#", "#..#.", ".##.#"], // &
+    ["..#..", "..#..", ".....", ".....", ".....", ".....", "....."], // '
+    ["...#.", "..#..", ".#...", ".#...", ".#...", "..#..", "...#."], // (
+    [".#...", "..#..", "...#.", "...#.", "...#.", "..#..", ".#..."], // )
+    [".....", "..#..", "#.#.#", ".###.", "#.#.#", "..#..", "....."], // *
+    [".....", "..#..", "..#..", "#####", "..#..", "..#..", "....."], // +
+    [".....", ".....", ".....", ".....", ".##..", "..#..", ".#..."], // ,
+    [".....", ".....", ".....", "#####", ".....", ".....", "....."], // -
+    [".....", ".....", ".....", ".....", ".....", ".##..", ".##.."], // .
+    [".....", "....#", "...#.", "..#..", ".#...", "#....", "....."], // /
+    [".###.", "#...#", "#..##", "#.#.#", "##..#", "#...#", ".###."], // 0
+    ["..#..", ".##..", "..#..", "..#..", "..#..", "..#..", ".###."], // 1
+    [".###.", "#...#", "....#", "...#.", "..#..", ".#...", "#####"], // 2
+    ["#####", "...#.", "..#..", "...#.", "....#", "#...#", ".###."], // 3
+    ["...#.", "..##.", ".#.#.", "#..#.", "#####", "...#.", "...#."], // 4
+    ["#####", "#....", "####.", "....#", "....#", "#...#", ".###."], // 5
+    ["..##.", ".#...", "#....", "####.", "#...#", "#...#", ".###."], // 6
+    ["#####", "....#", "...#.", "..#..", ".#...", ".#...", ".#..."], // 7
+    [".###.", "#...#", "#...#", ".###.", "#...#", "#...#", ".###."], // 8
+    [".###.", "#...#", "#...#", ".####", "....#", "...#.", ".##.."], // 9
+    [".....", ".##..", ".##..", ".....", ".##..", ".##..", "....."], // :
+    [".....", ".##..", ".##..", ".....", ".##..", "..#..", ".#..."], // ;
+    ["...#.", "..#..", ".#...", "#....", ".#...", "..#..", "...#."], // <
+    [".....", ".....", "#####", ".....", "#####", ".....", "....."], // =
+    [".#...", "..#..", "...#.", "....#", "...#.", "..#..", ".#..."], // >
+    [".###.", "#...#", "....#", "...#.", "..#..", ".....", "..#.."], // ?
+    [".###.", "#...#", "....#", ".##.#", "#.#.#", "#.#.#", ".###."], // @
+    [".###.", "#...#", "#...#", "#####", "#...#", "#...#", "#...#"], // A
+    ["####.", "#...#", "#...#", "####.", "#...#", "#...#", "####."], // B
+    [".###.", "#...#", "#....", "#....", "#....", "#...#", ".###."], // C
+    ["###..", "#..#.", "#...#", "#...#", "#...#", "#..#.", "###.."], // D
+    ["#####", "#....", "#....", "####.", "#....", "#....", "#####"], // E
+    ["#####", "#....", "#....", "####.", "#....", "#....", "#...."], // F
+    [".###.", "#...#", "#....", "#.###", "#...#", "#...#", ".####"], // G
+    ["#...#", "#...#", "#...#", "#####", "#...#", "#...#", "#...#"], // H
+    [".###.", "..#..", "..#..", "..#..", "..#..", "..#..", ".###."], // I
+    ["..###", "...#.", "...#.", "...#.", "...#.", "#..#.", ".##.."], // J
+    ["#...#", "#..#.", "#.#..", "##...", "#.#..", "#..#.", "#...#"], // K
+    ["#....", "#....", "#....", "#....", "#....", "#....", "#####"], // L
+    ["#...#", "##.##", "#.#.#", "#.#.#", "#...#", "#...#", "#...#"], // M
+    ["#...#", "#...#", "##..#", "#.#.#", "#..##", "#...#", "#...#"], // N
+    [".###.", "#...#", "#...#", "#...#", "#...#", "#...#", ".###."], // O
+    ["####.", "#...#", "#...#", "####.", "#....", "#....", "#...."], // P
+    [".###.", "#...#", "#...#", "#...#", "#.#.#", "#..#.", ".##.#"], // Q
+    ["####.", "#...#", "#...#", "####.", "#.#..", "#..#.", "#...#"], // R
+    [".####", "#....", "#....", ".###.", "....#", "....#", "####."], // S
+    ["#####", "..#..", "..#..", "..#..", "..#..", "..#..", "..#.."], // T
+    ["#...#", "#...#", "#...#", "#...#", "#...#", "#...#", ".###."], // U
+    ["#...#", "#...#", "#...#", "#...#", "#...#", ".#.#.", "..#.."], // V
+    ["#...#", "#...#", "#...#", "#.#.#", "#.#.#", "#.#.#", ".#.#."], // W
+    ["#...#", "#...#", ".#.#.", "..#..", ".#.#.", "#...#", "#...#"], // X
+    ["#...#", "#...#", ".#.#.", "..#..", "..#..", "..#..", "..#.."], // Y
+    ["#####", "....#", "...#.", "..#..", ".#...", "#....", "#####"], // Z
+    [".###.", ".#...", ".#...", ".#...", ".#...", ".#...", ".###."], // [
+    [".....", "#....", ".#...", "..#..", "...#.", "....#", "....."], // backslash
+    [".###.", "...#.", "...#.", "...#.", "...#.", "...#.", ".###."], // ]
+    ["..#..", ".#.#.", "#...#", ".....", ".....", ".....", "....."], // ^
+    [".....", ".....", ".....", ".....", ".....", ".....", "#####"], // _
+    [".#...", "..#..", ".....", ".....", ".....", ".....", "....."], // `
+    [".....", ".....", ".###.", "....#", ".####", "#...#", ".####"], // a
+    ["#....", "#....", "#.##.", "##..#", "#...#", "#...#", "####."], // b
+    [".....", ".....", ".###.", "#....", "#....", "#...#", ".###."], // c
+    ["....#", "....#", ".##.#", "#..##", "#...#", "#...#", ".####"], // d
+    [".....", ".....", ".###.", "#...#", "#####", "#....", ".###."], // e
+    ["..##.", ".#..#", ".#...", "###..", ".#...", ".#...", ".#..."], // f
+    [".....", ".####", "#...#", "#...#", ".####", "....#", ".###."], // g
+    ["#....", "#....", "#.##.", "##..#", "#...#", "#...#", "#...#"], // h
+    ["..#..", ".....", ".##..", "..#..", "..#..", "..#..", ".###."], // i
+    ["...#.", ".....", "..##.", "...#.", "...#.", "#..#.", ".##.."], // j
+    ["#....", "#....", "#..#.", "#.#..", "##...", "#.#..", "#..#."], // k
+    [".##..", "..#..", "..#..", "..#..", "..#..", "..#..", ".###."], // l
+    [".....", ".....", "##.#.", "#.#.#", "#.#.#", "#...#", "#...#"], // m
+    [".....", ".....", "#.##.", "##..#", "#...#", "#...#", "#...#"], // n
+    [".....", ".....", ".###.", "#...#", "#...#", "#...#", ".###."], // o
+    [".....", ".....", "####.", "#...#", "####.", "#....", "#...."], // p
+    [".....", ".....", ".##.#", "#..##", ".####", "....#", "....#"], // q
+    [".....", ".....", "#.##.", "##..#", "#....", "#....", "#...."], // r
+    [".....", ".....", ".###.", "#....", ".###.", "....#", "####."], // s
+    [".#...", ".#...", "###..", ".#...", ".#...", ".#..#", "..##."], // t
+    [".....", ".....", "#...#", "#...#", "#...#", "#..##", ".##.#"], // u
+    [".....", ".....", "#...#", "#...#", "#...#", ".#.#.", "..#.."], // v
+    [".....", ".....", "#...#", "#...#", "#.#.#", "#.#.#", ".#.#."], // w
+    [".....", ".....", "#...#", ".#.#.", "..#..", ".#.#.", "#...#"], // x
+    [".....", ".....", "#...#", "#...#", ".####", "....#", ".###."], // y
+    [".....", ".....", "#####", "...#.", "..#..", ".#...", "#####"], // z
+    ["...#.", "..#..", "..#..", ".#...", "..#..", "..#..", "...#."], // {
+    ["..#..", "..#..", "..#..", "..#..", "..#..", "..#..", "..#.."], // |
+    [".#...", "..#..", "..#..", "...#.", "..#..", "..#..", ".#..."], // }
+    [".....", ".....", ".#...", "#.#.#", "...#.", ".....", "....."], // ~
+];
+
+fn draw_font(atlas: &mut ImageBufferA) {
+    for (k, glyph) in GLYPHS.iter().enumerate() {
+        for j in 0..GLYPH_H {
+            for i in 0..GLYPH_W {
+                let on = glyph.get(j).and_then(|row| row.as_bytes().get(i)) == Some(&b'#');
+                let px = if on { (255, 255, 255, 255) } else { (0, 0, 0, 0) };
+                atlas.set_px(FONT_ORIGIN.0 + k * GLYPH_W + i, FONT_ORIGIN.1 + j, px);
+            }
+        }
+    }
+}
+
+enum Shape {
+    Circle,
+    Square,
+}
+
+struct PlaceholderDef {
+    name: &'static str,    // in atlas.json
+    cell: (usize, usize),
+    shape: Shape,
+    colour: (u8, u8, u8),
+}
+
+// enemies match their colours in ENEMY_DEFS, the ghost is opaque here because the game draws it see through
+const SPRITES: [PlaceholderDef; 10] = [
+    PlaceholderDef { name: "player", cell: (0, 8), shape: Shape::Circle, colour: (153, 0, 0) },
+    PlaceholderDef { name: "scrap", cell: (1, 8), shape: Shape::Circle, colour: (0, 0, 255) },
+    PlaceholderDef { name: "enemy_grunt", cell: (2, 8), shape: Shape::Circle, colour: (0, 0, 0) },
+    PlaceholderDef { name: "enemy_runner", cell: (3, 8), shape: Shape::Circle, colour: (115, 13, 13) },
+    PlaceholderDef { name: "enemy_brute", cell: (4, 8), shape: Shape::Circle, colour: (51, 38, 77) },
+    PlaceholderDef { name: "enemy_spitter", cell: (5, 8), shape: Shape::Circle, colour: (64, 102, 13) },
+    PlaceholderDef { name: "enemy_wrecker", cell: (6, 8), shape: Shape::Circle, colour: (115, 77, 26) },
+    PlaceholderDef { name: "enemy_ghost", cell: (7, 8), shape: Shape::Circle, colour: (204, 217, 255) },
+    PlaceholderDef { name: "wall_1", cell: (8, 8), shape: Shape::Square, colour: (128, 128, 128) },
+    PlaceholderDef { name: "wall_2", cell: (9, 8), shape: Shape::Square, colour: (166, 166, 166) },
+];
+
+// how a frame of an animation differs from the plain sprite
+#[derive(Clone, Copy)]
+enum Pose {
+    Still,
+    Squash(f32),    // this much of the height, sat on the bottom of the cell
+    Shrink(f32, f32),   // this much of the size, raised this many pixels
+    Cracked(usize), // the first this many of CRACKS drawn over it
+}
+
+struct PlaceholderAnimationDef {
+    name: &'static str,    // in atlas.json
+    first_cell: (usize, usize), // the frames go along the row from here
+    shape: Shape,
+    colour: (u8, u8, u8),
+    frames: &'static [Pose],
+    duration: f32,  // of each frame
+    looping: bool,
+}
+
+const WALK: [Pose; 2] = [Pose::Still, Pose::Squash(0.875)];
+const BOB: [Pose; 4] = [Pose::Shrink(0.75, 0.0), Pose::Shrink(0.75, 2.0), Pose::Shrink(0.75, 4.0), Pose::Shrink(0.75, 2.0)];
+const FLASH: [Pose; 3] = [Pose::Shrink(1.0, 0.0), Pose::Shrink(0.625, 0.0), Pose::Shrink(0.375, 0.0)];
+const DAMAGE: [Pose; 4] = [Pose::Cracked(0), Pose::Cracked(1), Pose::Cracked(2), Pose::Cracked(3)];
+
+// in pixels of a cell
+const CRACKS: [((f32, f32), (f32, f32)); 3] = [
+    ((4.0, 6.0), (20.0, 14.0)),
+    ((26.0, 4.0), (12.0, 27.0)),
+    ((5.0, 24.0), (28.0, 21.0)),
+];
+
+// same colours as SPRITES. muzzle_flash plays once over ARC_DURATION and the damage ones go by how hurt
+// the wall is, so they dont loop
+const ANIMATIONS: [PlaceholderAnimationDef; 11] = [
+    PlaceholderAnimationDef { name: "player_walk", first_cell: (0, 9), shape: Shape::Circle, colour: (153, 0, 0), frames: &WALK, duration: 0.125, looping: true },
+    PlaceholderAnimationDef { name: "enemy_grunt_walk", first_cell: (2, 9), shape: Shape::Circle, colour: (0, 0, 0), frames: &WALK, duration: 0.25, looping: true },
+    PlaceholderAnimationDef { name: "enemy_runner_walk", first_cell: (4, 9), shape: Shape::Circle, colour: (115, 13, 13), frames: &WALK, duration: 0.125, looping: true },
+    PlaceholderAnimationDef { name: "enemy_brute_walk", first_cell: (6, 9), shape: Shape::Circle, colour: (51, 38, 77), frames: &WALK, duration: 0.375, looping: true },
+    PlaceholderAnimationDef { name: "enemy_spitter_walk", first_cell: (8, 9), shape: Shape::Circle, colour: (64, 102, 13), frames: &WALK, duration: 0.25, looping: true },
+    PlaceholderAnimationDef { name: "enemy_wrecker_walk", first_cell: (10, 9), shape: Shape::Circle, colour: (115, 77, 26), frames: &WALK, duration: 0.25, looping: true },
+    PlaceholderAnimationDef { name: "enemy_ghost_walk", first_cell: (12, 9), shape: Shape::Circle, colour: (204, 217, 255), frames: &WALK, duration: 0.375, looping: true },
+    PlaceholderAnimationDef { name: "scrap_bob", first_cell: (0, 10), shape: Shape::Circle, colour: (0, 0, 255), frames: &BOB, duration: 0.25, looping: true },
+    PlaceholderAnimationDef { name: "muzzle_flash", first_cell: (4, 10), shape: Shape::Circle, colour: (255, 230, 128), frames: &FLASH, duration: 0.02, looping: false },
+    PlaceholderAnimationDef { name: "wall_1_damage", first_cell: (7, 10), shape: Shape::Square, colour: (128, 128, 128), frames: &DAMAGE, duration: 0.25, looping: false },
+    PlaceholderAnimationDef { name: "wall_2_damage", first_cell: (11, 10), shape: Shape::Square, colour: (166, 166, 166), frames: &DAMAGE, duration: 0.25, looping: false },
+];
+
+// filled with a darker rim, whatevers outside the shape is cleared
+fn draw_shape(atlas: &mut ImageBufferA, cell: (usize, usize), shape: &Shape, colour: (u8, u8, u8), pose: Pose) {
+    let rim = (colour.0 / 5 * 3, colour.1 / 5 * 3, colour.2 / 5 * 3, 255);
+    let fill = (colour.0, colour.1, colour.2, 255);
+    let size = ATLAS_CELL as f32;
+    let c = size / 2.0;
+    for j in 0..ATLAS_CELL {
+        for i in 0..ATLAS_CELL {
+            // where this pixel is on the unposed shape, and how much smaller the shape got
+            let (x, y) = (i as f32 + 0.5, j as f32 + 0.5);
+            let (x, y, scale) = match pose {
+                Pose::Squash(s) => (x, size - (size - y) / s, s),
+                Pose::Shrink(s, lift) => (c + (x - c) / s, c + (y - c + lift) / s, s),
+                _ => (x, y, 1.0),
+            };
+            // how far in from the edge of the shape
+            let inside = scale * match shape {
+                Shape::Circle => c - 0.5 - (x - c).hypot(y - c),
+                Shape::Square => x.min(y).min(size - x).min(size - y) - 0.5,
+            };
+            let cracked = match pose {
+                Pose::Cracked(n) => CRACKS[..n].iter().any(|(a, b)| dist_to_segment((i as f32 + 0.5, j as f32 + 0.5), *a, *b) < 1.0),
+                _ => false,
+            };
+            let px = if inside < 0.0 {
+                (0, 0, 0, 0)
+            } else if inside < 2.0 || cracked {
+                rim
+            } else {
+                fill
+            };
+            atlas.set_px(cell.0 * ATLAS_CELL + i, cell.1 * ATLAS_CELL + j, px);
+        }
+    }
+}
+
+fn dist_to_segment(p: (f32, f32), a: (f32, f32), b: (f32, f32)) -> f32 {
+    let (dx, dy) = (b.0 - a.0, b.1 - a.1);
+    let t = (((p.0 - a.0) * dx + (p.1 - a.1) * dy) / (dx * dx + dy * dy)).clamp(0.0, 1.0);
+    (p.0 - a.0 - t * dx).hypot(p.1 - a.1 - t * dy)
+}
+
+fn draw_placeholders(atlas: &mut ImageBufferA) {
+    for def in SPRITES.iter() {
+        draw_shape(atlas, def.cell, &def.shape, def.colour, Pose::Still);
+    }
+    for def in ANIMATIONS.iter() {
+        for (k, pose) in def.frames.iter().enumerate() {
+            draw_shape(atlas, (def.first_cell.0 + k, def.first_cell.1), &def.shape, def.colour, *pose);
+        }
+    }
+}
+
+fn main() {
+    let path = std::env::args().nth(1).unwrap_or("atlas.png".to_string());
+    let mut atlas = ImageBufferA::new_from_file(&path).unwrap_or_else(|| panic!("couldn't read {}", path));
+    if atlas.w < FONT_ORIGIN.0 + GLYPHS.len() * GLYPH_W || atlas.h < FONT_ORIGIN.1 + GLYPH_H {
+        panic!("{} is too small for the font", path);
+    }
+    draw_font(&mut atlas);
+    draw_placeholders(&mut atlas);
+    atlas.dump_to_file(&path);
+    let names: Vec<&str> = SPRITES.iter().map(|def| def.name).collect();
+    println!("drew {} glyphs and {} into {}", GLYPHS.len(), names.join(", "), path);
+    for def in ANIMATIONS.iter() {
+        println!("{}: {} frames of {}s{}", def.name, def.frames.len(), def.duration, if def.looping {", looping"} else {""});
+    }
+}
+
+#[test]
+fn test_atlas() {
+    for glyph in GLYPHS.iter() {
+        assert!(glyph.iter().all(|row| row.len() == 5 && row.chars().all(|c| c == '#' || c == '.')));
+    }
+    // the checked in atlas is up to date
+    let dir = env!("CARGO_MANIFEST_DIR");
+    let path = format!("{}/atlas.png", dir);
+    let atlas = ImageBufferA::new_from_file(&path).unwrap();
+    let mut drawn = ImageBufferA::new_from_file(&path).unwrap();
+    draw_font(&mut drawn);
+    draw_placeholders(&mut drawn);
+    assert!(atlas.pixels == drawn.pixels, "atlas.png is out of date, run gen-atlas");
+
+    // and atlas.json points at them
+    let manifest: serde_json::Value = serde_json::from_str(&std::fs::read_to_string(format!("{}/atlas.json", dir)).unwrap()).unwrap();
+    for def in SPRITES.iter() {
+        assert_eq!(manifest["sprites"][def.name]["cell"], serde_json::json!([def.cell.0, def.cell.1]), "{} in atlas.json", def.name);
+    }
+    for def in ANIMATIONS.iter() {
+        let animation = &manifest["animations"][def.name];
+        assert_eq!(animation["looping"], serde_json::json!(def.looping), "{} in atlas.json", def.name);
+        let frames = animation["frames"].as_array().unwrap_or_else(|| panic!("{} in atlas.json", def.name));
+        assert_eq!(frames.len(), def.frames.len(), "{} in atlas.json", def.name);
+        for (k, frame) in frames.iter().enumerate() {
+            assert_eq!(frame["cell"], serde_json::json!([def.first_cell.0 + k, def.first_cell.1]), "{} in atlas.json", def.name);
+            assert_eq!(frame["duration"].as_f64().map(|d| d as f32), Some(def.duration), "{} in atlas.json", def.name);
+        }
+    }
+}
@@ -0,0 +1,228 @@
+// Packs a directory of pngs into the free space of the hand made atlas and writes the result and a
+// manifest of where each one went, in the atlas.json format the game loads (sprites by uv rect,
+// named after the file, next to everything that was already in there):
+//  cargo run --bin pack-atlas -- <dir of pngs> <atlas.png> <atlas.json> <out.png> <out.json> [--padding N] [--bleed N]
+// Any cell with something drawn in it or named in atlas.json is left alone, so the font and the cell
+// addressed sprites survive, and it stays the same size since krenderer assumes ATLAS_SIZE.
+// Each sprite gets padding pixels of space around it and the outer bleed pixels of that are its own
+// edge smeared outwards, so sampling right on the edge doesnt pick up a neighbour.
+// Inputs need to be 8 bit rgba.
+
+#[allow(dead_code)]
+#[path = "../lib/kimg.rs"]
+mod kimg;
+
+use kimg::*;
+
+use serde_json::{json, Value};
+
+const ATLAS_CELL: usize = 32;
+
+// x, y, w, h in pixels
+type PxRect = (usize, usize, usize, usize);
+
+fn overlaps(a: PxRect, b: PxRect) -> bool {
+    a.0 < b.0 + b.2 && b.0 < a.0 + a.2 && a.1 < b.1 + b.3 && b.1 < a.1 + a.3
+}
+
+// shelf packing, tallest first, stepping past anything reserved. None if it doesnt fit in w x h
+fn pack(sizes: &[(usize, usize)], padding: usize, size: (usize, usize), reserved: &[PxRect]) -> Option<Vec<(usize, usize)>> {
+    let mut order: Vec<usize> = (0..sizes.len()).collect();
+    order.sort_by_key(|&i| (std::cmp::Reverse(sizes[i].1), std::cmp::Reverse(sizes[i].0), i));
+
+    let mut placed = vec![(0, 0); sizes.len()];
+    let (mut x, mut y, mut shelf_h) = (0, 0, 0);
+    for i in order {
+        let (w, h) = (sizes[i].0 + 2 * padding, sizes[i].1 + 2 * padding);
+        loop {
+            if x + w > size.0 {
+                // nothing on this shelf yet so go down to where the first thing in the way ends
+                y = if shelf_h > 0 {
+                    y + shelf_h
+                } else {
+                    reserved.iter().filter(|r| overlaps(**r, (0, y, size.0, h))).map(|r| r.1 + r.3).min()?
+                };
+                x = 0;
+                shelf_h = 0;
+            }
+            if y + h > size.1 {
+                return None;
+            }
+            match reserved.iter().find(|r| overlaps(**r, (x, y, w, h))) {
+                Some(r) => x = r.0 + r.2,
+                None => break,
+            }
+        }
+        placed[i] = (x + padding, y + padding);
+        x += w;
+        shelf_h = shelf_h.max(h);
+    }
+    Some(placed)
+}
+
+// every cell with anything drawn in it, and whatever the manifest says is in use even if its blank
+fn reserved_rects(atlas: &ImageBufferA, manifest: &Value) -> Vec<PxRect> {
+    let mut reserved = Vec::new();
+    for cy in 0..atlas.h.div_ceil(ATLAS_CELL) {
+        for cx in 0..atlas.w.div_ceil(ATLAS_CELL) {
+            let (x, y) = (cx * ATLAS_CELL, cy * ATLAS_CELL);
+            let (w, h) = (ATLAS_CELL.min(atlas.w - x), ATLAS_CELL.min(atlas.h - y));
+            if (y..y + h).any(|j| (x..x + w).any(|i| atlas.get_px(i, j).3 > 0)) {
+                reserved.push((x, y, w, h));
+            }
+        }
+    }
+    let frames = manifest["animations"].as_object().into_iter().flat_map(|a| a.values())
+        .flat_map(|a| a["frames"].as_array().into_iter().flatten());
+    for def in manifest["sprites"].as_object().into_iter().flat_map(|s| s.values()).chain(frames) {
+        let n = |v: &Value, default: f64| v.as_f64().unwrap_or(default);
+        if def["uv"].is_array() {
+            let uv = &def["uv"];
+            let (w, h) = (atlas.w as f64, atlas.h as f64);
+            let x = (n(&uv[0], 0.0) * w) as usize;
+            let y = (n(&uv[1], 0.0) * h) as usize;
+            reserved.push((x, y, (n(&uv[2], 0.0) * w).ceil() as usize + 1, (n(&uv[3], 0.0) * h).ceil() as usize + 1));
+        } else {
+            let (cell, cells) = (&def["cell"], &def["cells"]);
+            reserved.push((
+                n(&cell[0], 0.0) as usize * ATLAS_CELL,
+                n(&cell[1], 0.0) as usize * ATLAS_CELL,
+                n(&cells[0], 1.0) as usize * ATLAS_CELL,
+                n(&cells[1], 1.0) as usize * ATLAS_CELL,
+            ));
+        }
+    }
+    reserved
+}
+
+// copy src in at (x, y) and smear its edges out bleed pixels
+fn blit(atlas: &mut ImageBufferA, src: &ImageBufferA, x: usize, y: usize, bleed: usize) {
+    for j in 0..src.h + 2 * bleed {
+        for i in 0..src.w + 2 * bleed {
+            let si = (i as i64 - bleed as i64).max(0).min(src.w as i64 - 1) as usize;
+            let sj = (j as i64 - bleed as i64).max(0).min(src.h as i64 - 1) as usize;
+            atlas.set_px(x + i - bleed, y + j - bleed, src.get_px(si, sj));
+        }
+    }
+}
+
+fn is_rgba8(path: &std::path::Path) -> bool {
+    let file = match std::fs::File::open(path) {
+        Ok(f) => f,
+        Err(_) => return false,
+    };
+    match png::Decoder::new(file).read_info() {
+        Ok(reader) => reader.info().color_type == png::ColorType::Rgba && reader.info().bit_depth == png::BitDepth::Eight,
+        Err(_) => false,
+    }
+}
+
+fn main() {
+    let args: Vec<String> = std::env::args().collect();
+    let arg_value = |flag: &str| args.iter().position(|a| a == flag).and_then(|i| args.get(i + 1)).map(|v| v.parse::<usize>().unwrap_or_else(|_| panic!("{} wants a number", flag)));
+    // whatevers left after the flags and their values
+    let mut positional = Vec::new();
+    let mut i = 1;
+    while i < args.len() {
+        if args[i].starts_with("--") {
+            i += 2;
+        } else {
+            positional.push(&args[i]);
+            i += 1;
+        }
+    }
+    if positional.len() != 5 {
+        println!("usage: pack-atlas <dir of pngs> <atlas.png> <atlas.json> <out.png> <out.json> [--padding N] [--bleed N]");
+        std::process::exit(1);
+    }
+    let (in_dir, base_png, base_json, out_png, out_json) = (positional[0], positional[1], positional[2], positional[3], positional[4]);
+    if out_png == base_png || out_json == base_json {
+        println!("not overwriting {} or {}, write somewhere else and copy it over once its been checked", base_png, base_json);
+        std::process::exit(1);
+    }
+    let padding = arg_value("--padding").unwrap_or(2);
+    let bleed = arg_value("--bleed").unwrap_or(1).min(padding);
+
+    let mut atlas = ImageBufferA::new_from_file(base_png).unwrap_or_else(|| panic!("couldn't read {}", base_png));
+    let mut manifest: Value = std::fs::read_to_string(base_json).map_err(|e| e.to_string())
+        .and_then(|s| serde_json::from_str(&s).map_err(|e| e.to_string()))
+        .unwrap_or_else(|e| panic!("couldn't read {}: {}", base_json, e));
+    let reserved = reserved_rects(&atlas, &manifest);
+
+    let mut paths: Vec<std::path::PathBuf> = std::fs::read_dir(in_dir).unwrap_or_else(|e| panic!("couldn't read {}: {}", in_dir, e))
+        .filter_map(|e| e.ok().map(|e| e.path()))
+        .filter(|p| p.extension().is_some_and(|ext| ext == "png"))
+        .collect();
+    paths.sort();
+
+    let mut names = Vec::new();
+    let mut images = Vec::new();
+    for path in paths {
+        if !is_rgba8(&path) {
+            println!("skipping {}, not 8 bit rgba", path.display());
+            continue;
+        }
+        let name = path.file_stem().unwrap().to_string_lossy().to_string();
+        if !manifest["sprites"][&name].is_null() {
+            panic!("{} is already in {}", name, base_json);
+        }
+        let image = ImageBufferA::new_from_file(path.to_str().unwrap()).unwrap();
+        names.push(name);
+        images.push(image);
+    }
+    let sizes: Vec<(usize, usize)> = images.iter().map(|im| (im.w, im.h)).collect();
+
+    let placed = pack(&sizes, padding, (atlas.w, atlas.h), &reserved)
+        .unwrap_or_else(|| panic!("doesnt fit in whats free of {}x{}", atlas.w, atlas.h));
+
+    let (w, h) = (atlas.w as f32, atlas.h as f32);
+    for i in 0..images.len() {
+        let (x, y) = placed[i];
+        blit(&mut atlas, &images[i], x, y, bleed);
+        manifest["sprites"][&names[i]] = json!({
+            "uv": [x as f32 / w, y as f32 / h, images[i].w as f32 / w, images[i].h as f32 / h],
+        });
+    }
+    atlas.dump_to_file(out_png);
+    std::fs::write(out_json, serde_json::to_string_pretty(&manifest).unwrap()).unwrap_or_else(|e| panic!("couldn't write {}: {}", out_json, e));
+    println!("packed {} sprites into {}x{}", images.len(), atlas.w, atlas.h);
+}
+
+#[test]
+fn test_pack() {
+    let sizes = [(10, 20), (30, 5), (16, 16), (8, 8), (20, 30)];
+    let padding = 2;
+    let reserved = [(0, 0, 32, 32), (64, 64, 32, 32)];
+    let placed = pack(&sizes, padding, (96, 96), &reserved).unwrap();
+    // padded rects stay inside and dont overlap each other or whats reserved
+    let rect = |i: usize| (placed[i].0 - padding, placed[i].1 - padding, sizes[i].0 + 2 * padding, sizes[i].1 + 2 * padding);
+    for i in 0..sizes.len() {
+        let (x, y, w, h) = rect(i);
+        assert!(x + w <= 96 && y + h <= 96);
+        for r in reserved.iter() {
+            assert!(!overlaps(rect(i), *r), "{} is on reserved {:?}", i, r);
+        }
+        for j in 0..i {
+            assert!(!overlaps(rect(i), rect(j)), "{} and {} overlap", i, j);
+        }
+    }
+    assert!(pack(&sizes, padding, (64, 64), &[]).is_some());
+    assert!(pack(&sizes, padding, (32, 32), &[]).is_none());
+    assert!(pack(&[(8, 8)], padding, (32, 32), &[(0, 0, 32, 32)]).is_none());
+
+    // drawn in cells and ones the manifest names are reserved
+    let mut atlas = ImageBufferA::new(64, 64);
+    atlas.set_px(40, 5, (255, 255, 255, 255));
+    let manifest = json!({ "sprites": { "book": { "cell": [0, 1], "cells": [2, 1] } }, "animations": {} });
+    assert_eq!(reserved_rects(&atlas, &manifest), vec![(32, 0, 32, 32), (0, 32, 64, 32)]);
+
+    // edges smear out into the padding
+    let mut src = ImageBufferA::new(2, 1);
+    src.set_px(0, 0, (255, 0, 0, 255));
+    src.set_px(1, 0, (0, 255, 0, 255));
+    let mut atlas = ImageBufferA::new(8, 8);
+    blit(&mut atlas, &src, 2, 2, 1);
+    assert_eq!(atlas.get_px(1, 1), (255, 0, 0, 255));
+    assert_eq!(atlas.get_px(4, 3), (0, 255, 0, 255));
+    assert_eq!(atlas.get_px(0, 0), (0, 0, 0, 0));
+}
@@ -0,0 +1,146 @@
+use std::cmp::Reverse;
+use std::collections::BinaryHeap;
+
+// orthogonal then diagonal, costs are 10 and 14 so its all integers
+const NEIGHBOURS: [(i32, i32, i32); 8] = [
+    (1, 0, 10), (-1, 0, 10), (0, 1, 10), (0, -1, 10),
+    (1, 1, 14), (1, -1, 14), (-1, 1, 14), (-1, -1, 14),
+];
+
+// Distance to the goal cell over a square of cells around it, going around blocked cells.
+// Follow it downhill to get to the goal. Cells that cant reach the goal have no distance.
+#[derive(Default)]
+pub struct FlowField {
+    pub goal: Option<(i32, i32)>,
+    radius: i32,
+    dist: Vec<Option<i32>>,
+}
+
+impl FlowField {
+    pub fn compute(goal: (i32, i32), radius: i32, blocked: impl Fn((i32, i32)) -> bool) -> FlowField {
+        let w = 2 * radius + 1;
+        let mut ff = FlowField {
+            goal: Some(goal),
+            radius,
+            dist: vec![None; (w * w) as usize],
+        };
+
+        // dijkstra
+        let mut queue = BinaryHeap::new();
+        let goal_idx = ff.index(goal).unwrap();
+        ff.dist[goal_idx] = Some(0);
+        queue.push(Reverse((0, goal.0, goal.1)));
+        while let Some(Reverse((d, i, j))) = queue.pop() {
+            if ff.dist[ff.index((i, j)).unwrap()].is_some_and(|best| d > best) {
+                continue;
+            }
+            for (di, dj, cost) in NEIGHBOURS {
+                let n = (i + di, j + dj);
+                let n_idx = match ff.index(n) {
+                    Some(idx) => idx,
+                    None => continue,
+                };
+                if blocked(n) {
+                    continue;
+                }
+                // no cutting corners between two buildings
+                if di != 0 && dj != 0 && (blocked((i + di, j)) || blocked((i, j + dj))) {
+                    continue;
+                }
+                if ff.dist[n_idx].is_none_or(|best| d + cost < best) {
+                    ff.dist[n_idx] = Some(d + cost);
+                    queue.push(Reverse((d + cost, n.0, n.1)));
+                }
+            }
+        }
+
+        ff
+    }
+
+    fn index(&self, cell: (i32, i32)) -> Option<usize> {
+        let goal = self.goal?;
+        let x = cell.0 - goal.0 + self.radius;
+        let y = cell.1 - goal.1 + self.radius;
+        let w = 2 * self.radius + 1;
+        if x < 0 || y < 0 || x >= w || y >= w {
+            return None;
+        }
+        Some((y * w + x) as usize)
+    }
+
+    pub fn dist(&self, cell: (i32, i32)) -> Option<i32> {
+        self.index(cell).and_then(|idx| self.dist[idx])
+    }
+
+    // the neighbouring cell thats the most downhill, None if already at the goal or cant get there
+    pub fn next_cell(&self, cell: (i32, i32)) -> Option<(i32, i32)> {
+        let d = self.dist(cell)?;
+        let mut best = None;
+        let mut best_d = d;
+        for (di, dj, _) in NEIGHBOURS {
+            let n = (cell.0 + di, cell.1 + dj);
+            // same no cutting corners as compute. buildings never get a distance, and any open cell
+            // next to one that does gets one too, so no distance on either side means its blocked
+            if di != 0 && dj != 0 && (self.dist((cell.0 + di, cell.1)).is_none() || self.dist((cell.0, cell.1 + dj)).is_none()) {
+                continue;
+            }
+            if let Some(nd) = self.dist(n) {
+                if nd < best_d {
+                    best_d = nd;
+                    best = Some(n);
+                }
+            }
+        }
+        best
+    }
+}
+
+#[test]
+fn test_flow_field() {
+    // wall along x = 2 from y = -5 to 5 with a gap at y = 3
+    let blocked = |c: (i32, i32)| c.0 == 2 && c.1 >= -5 && c.1 <= 5 && c.1 != 3;
+    let ff = FlowField::compute((0, 0), 10, blocked);
+    assert_eq!(ff.dist((0, 0)), Some(0));
+    assert_eq!(ff.dist((2, 0)), None);
+
+    // from behind the wall, walk downhill and it has to go through the gap
+    let mut cell = (4, 0);
+    let mut went_through_gap = false;
+    for _ in 0..100 {
+        match ff.next_cell(cell) {
+            Some(n) => cell = n,
+            None => break,
+        }
+        went_through_gap |= cell == (2, 3);
+    }
+    assert_eq!(cell, (0, 0));
+    assert!(went_through_gap);
+
+    // boxed in means nowhere has a path
+    let boxed = |c: (i32, i32)| c.0.abs() == 2 && c.1.abs() <= 2 || c.1.abs() == 2 && c.0.abs() <= 2;
+    let ff = FlowField::compute((0, 0), 10, boxed);
+    assert_eq!(ff.dist((5, 5)), None);
+    assert_eq!(ff.next_cell((5, 5)), None);
+    assert_eq!(ff.dist((1, 1)), Some(14));
+}
+
+#[test]
+fn test_flow_field_diagonal_wall() {
+    // wall along x = y from -5 to 5, the cells only touch at the corners but thats still a wall
+    let blocked = |c: (i32, i32)| c.0 == c.1 && c.0.abs() <= 5;
+    let ff = FlowField::compute((3, -3), 10, blocked);
+    assert_ne!(ff.next_cell((0, 1)), Some((1, 0)));
+
+    // has to go round the end
+    let mut cell = (0, 1);
+    for _ in 0..100 {
+        let next = match ff.next_cell(cell) {
+            Some(n) => n,
+            None => break,
+        };
+        assert!(!blocked(next));
+        assert!(!(blocked((next.0, cell.1)) && blocked((cell.0, next.1))), "cut between walls from {:?} to {:?}", cell, next);
+        cell = next;
+    }
+    assert_eq!(cell, (3, -3));
+}
@@ -3,90 +3,93 @@ use std::f32::consts::PI;
 use crate::lib::kinput::*;
 use crate::lib::kmath::*;
 use crate::krenderer::*;
+use crate::simulation::*;
+use crate::save::*;
+use crate::profile::*;
+use crate::sprites::*;
 
 use glutin::event::VirtualKeyCode;
 
 pub struct Game {
     init: bool,
 
-    world_seed: u32,
-
     zoom_level: f32,
 
-    player_pos: Vec2,
-    player_vel: Vec2,
-    player_hp: f32,
-    player_scrap: i32,
-    player_next_t: f32,
-    player_draw_arc_t: f32,
-
     player_place_building_kind: Option<i32>,
+    player_weapon: i32,
+    pending_building_action: Option<BuildingAction>,   // held until a step picks it up
+    pending_upgrade_choice: Option<usize>,  // same
 
-    projectile_pos: Vec<Vec2>,
-    projectile_vel: Vec<Vec2>,
-    projectile_kind: Vec<i32>,
-
-    scrap_pos: Vec<Vec2>,
-    scrap_vel: Vec<Vec2>,
-
-    enemy_hp: Vec<f32>,
-    enemy_vel: Vec<Vec2>,
-    enemy_pos: Vec<Vec2>,
-    enemy_scrap: Vec<i32>, // coordinators can be big ones, maybe scrap spawns randomly? but coordinators at night good
-                        // maybe enemies die naturally causing random scrap. solar radiation
-                        // good to have closed form solutions, if we chunk we can load in
+    sim: Simulation,
+    sim_accumulator: f64,   // wall clock time not yet simulated
+    game_over_t: f64,       // how long the game over screen has been up
 
-    building_pos: Vec<(i32, i32)>,
-    building_hp: Vec<f32>,
-    building_next_t: Vec<f32>,  // cooldown to shoot, or next generated scrap
-    building_kind: Vec<i32>,
+    profile: Option<Profile>,   // None plays without any meta progression
+    profile_path: Option<String>,   // where to keep the profile, None to not touch the disk
+    banked: bool,   // this runs scrap has gone into the profile
 
     pub paused: bool,
-    t: f32,
+    pub sprites: SpriteSheet,   // empty unless theres a window to draw in
 }
 
-// todo text rendering
+// dont try to catch up more than this after a hitch, just run slow instead
+const MAX_FRAME_DT: f64 = 0.25;
+// so the click you died mashing doesnt restart straight away
+const GAME_OVER_DELAY: f64 = 1.0;
 
 impl Game {
-    pub fn new() -> Game {
+    pub fn new(profile: Option<Profile>, profile_path: Option<String>) -> Game {
         Game {
             init: true,
-            world_seed: 0,
 
             zoom_level: 1.0,
 
-            player_pos: Vec2::new(0.0, 0.0),
-            player_vel: Vec2::new(0.0, 0.0),
-            player_hp: 1.0,
-            player_scrap: 0,
-            player_next_t: 0.0,
-            player_draw_arc_t: 0.0,
-
             player_place_building_kind: None,
+            player_weapon: WEAPON_MELEE,
+            pending_building_action: None,
+            pending_upgrade_choice: None,
 
-            projectile_pos: Vec::new(),
-            projectile_vel: Vec::new(),
-            projectile_kind: Vec::new(),
+            sim: Simulation::new(0),
+            sim_accumulator: 0.0,
+            game_over_t: 0.0,
 
-            scrap_pos: vec![Vec2::new(1.0, 1.0), Vec2::new(1.5, 1.5)],
-            scrap_vel: vec![Vec2::new(0.0, 0.0), Vec2::new(0.0, 0.0)],
+            profile,
+            profile_path,
+            banked: false,
 
-            enemy_hp: Vec::new(),
-            enemy_vel: Vec::new(),
-            enemy_pos: Vec::new(),
-            enemy_scrap: Vec::new(),
+            paused: false,
+            sprites: SpriteSheet::default(),
+        }
+    }
 
-            building_pos: Vec::new(),
-            building_hp: Vec::new(),
-            building_next_t: Vec::new(),
-            building_kind: Vec::new(),
+    fn new_sim(&self, seed: u32) -> Simulation {
+        let mut sim = Simulation::new(seed);
+        if let Some(profile) = &self.profile {
+            profile.apply(&mut sim);
+        }
+        sim
+    }
 
-            t: 0.0,
-            paused: false,
+    fn save_profile(&self) {
+        if let (Some(profile), Some(path)) = (&self.profile, &self.profile_path) {
+            if let Err(e) = save_profile(path, profile) {
+                println!("couldn't save profile: {}", e);
+            }
         }
     }
 
     pub fn frame(&mut self, inputs: &FrameInputState, kc: &mut KRCanvas) {
+        self.update(inputs);
+
+        let (camera_rect, mouse_pos) = self.camera(inputs);
+        self.draw(inputs, camera_rect, mouse_pos, self.alpha(), kc);
+    }
+
+    // everything except drawing, so replays can run without a window
+    pub fn update(&mut self, inputs: &FrameInputState) {
+        if self.init {
+            self.sim = self.new_sim(inputs.seed);
+        }
 
         if inputs.scroll_up {
             self.zoom_level /= 1.1;
@@ -95,330 +98,348 @@ impl Game {
         }
         self.zoom_level = self.zoom_level.min(5.0).max(0.4);
 
-        let cam_w = self.zoom_level * inputs.screen_rect.w/inputs.screen_rect.h;
-        let cam_h = self.zoom_level;
-        let camera_rect = Rect::new_centered(self.player_pos.x, self.player_pos.y, cam_w, cam_h);
-        let mouse_pos = inputs.mouse_pos.transform(inputs.screen_rect, camera_rect);
-        let new_camera_center = camera_rect.centroid().lerp(mouse_pos, 0.2);
-        let camera_rect = Rect::new_centered(new_camera_center.x, new_camera_center.y, cam_w, cam_h);
-        let mouse_pos = inputs.mouse_pos.transform(inputs.screen_rect, camera_rect);
-        
-        let player_speed = 1.0;
-        let player_radius = 0.06;
-        let player_melee_radius = 0.3;
-        let player_melee_arc = PI/4.;
-        let arc_duration = 0.06;
-        let player_melee_cooldown = 0.5;
-        let player_melee_damage = 0.5;
-        let player_suck_pickup_radius = 0.5;
-        let suck_force = 10.0;
-
-        let scrap_radius = 0.02;
-
-        let enemy_radius = 0.03;
-        let enemy_radius_per_scrap = 0.02;
-        let enemy_steer_amount = 5.0;
-        let enemy_speed = 0.4;
-
-        let building_s = 0.2;
-
-        
-        
-        self.t += inputs.dt as f32;
-        
-        // 1.0 / -1.0 is nightfall
-        // 0.0 is dawn of a new day
-        let day_t = ((self.t / 200.0) % 1.0) * 2.0 - 1.0;
-
-        let day = day_t > 0.0;
-        let enemy_count = if day {
-            200
-        } else {
-            400
-        };
-
-        let enemy_acquisition_range = if day {
-            1.0
-        } else {
-            1.0
-        };
-
+        let (_, mouse_pos) = self.camera(inputs);
 
         // building
-        if inputs.just_pressed(VirtualKeyCode::Q) {
-            if self.player_place_building_kind == Some(0) {
-                self.player_place_building_kind = None;
-            } else {
-                self.player_place_building_kind = Some(0);
+        for (key, kind) in [(VirtualKeyCode::Q, BUILDING_WALL), (VirtualKeyCode::E, BUILDING_TURRET), (VirtualKeyCode::R, BUILDING_GENERATOR)] {
+            if inputs.just_pressed(key) && self.sim.unlocked_buildings.contains(&kind) {
+                if self.player_place_building_kind == Some(kind) {
+                    self.player_place_building_kind = None;
+                } else {
+                    self.player_place_building_kind = Some(kind);
+                }
             }
         }
 
-
-        let player_steer = {
-            let mut steer = Vec2::new(0.0, 0.0);
-            if inputs.pressed(VirtualKeyCode::W) {
-                steer.y = (steer.y - 1.0).max(-1.0);
-            }
-            if inputs.pressed(VirtualKeyCode::S) {
-                steer.y = (steer.y + 1.0).min(1.0);
-            }
-            if inputs.pressed(VirtualKeyCode::A) {
-                steer.x = (steer.x -  1.0).max(-1.0);
-            }
-            if inputs.pressed(VirtualKeyCode::D) {
-                steer.x = (steer.x + 1.0).min(1.0);
-            }
-            steer.normalize()
-        };
-        self.player_vel = 0.5 * self.player_vel;
-
-        let frame_vmag = (player_speed * player_steer + self.player_vel).magnitude();
-        let frame_vdir = (player_speed * player_steer + self.player_vel).normalize();
-        let frame_v = frame_vmag.min(player_speed) * frame_vdir;
-        self.player_pos = self.player_pos + frame_v * inputs.dt as f32;
-
-
-        // move scrap towards player
-        for (i, p) in self.scrap_pos.iter().enumerate() {
-            let vsp = self.player_pos - *p;
-            if vsp.magnitude() < (player_radius + scrap_radius + player_suck_pickup_radius) {
-                self.scrap_vel[i] = suck_force * inputs.dt as f32 * vsp.normalize() / vsp.magnitude();
-            } else {
-                self.scrap_vel[i] = Vec2::new(0.0, 0.0);
+        // weapons on the number keys, unless theres a level up to pick in which case its that
+        let number_keys = [VirtualKeyCode::Key1, VirtualKeyCode::Key2, VirtualKeyCode::Key3, VirtualKeyCode::Key4, VirtualKeyCode::Key5];
+        if self.sim.level_up_choices.is_empty() {
+            for (key, weapon) in number_keys.iter().zip([WEAPON_MELEE, WEAPON_PISTOL, WEAPON_SHOTGUN, WEAPON_FLAMETHROWER, WEAPON_GRENADE]) {
+                if inputs.just_pressed(*key) {
+                    self.player_weapon = weapon;
+                }
             }
-        }
-        
-        for i in 0..self.scrap_pos.len() {
-            self.scrap_pos[i] = self.scrap_pos[i] + self.scrap_vel[i] * inputs.dt as f32;
-        }
-
-        let mut dead_scrap = Vec::new(); // is this the best way to remove stuff from an array lol.
-        // could do a while loop and mutate it manually i guess and make sure to bail approriately
-        // because we might double spend if player and entities both pick up the scrap
-
-        // player picks up scrap
-        for (i, p) in self.scrap_pos.iter().enumerate() {
-            if (self.player_pos - *p).magnitude() < (player_radius + scrap_radius) {
-                self.player_scrap += 1;
-                dead_scrap.push(i);
+        } else {
+            for (i, key) in number_keys.iter().enumerate().take(self.sim.level_up_choices.len()) {
+                let clicked = inputs.lmb == KeyStatus::JustPressed && upgrade_card_rect(inputs.screen_rect, i).contains(inputs.mouse_pos);
+                if inputs.just_pressed(*key) || clicked {
+                    self.pending_upgrade_choice = Some(i);
+                }
             }
         }
 
-        for idx in dead_scrap.iter().rev() {
-            self.scrap_pos.swap_remove(*idx);
-            self.scrap_vel.swap_remove(*idx);
+        // survivor mode, weapons go off by themselves and scrap levels you up
+        if inputs.just_pressed(VirtualKeyCode::T) {
+            self.sim.survivor_mode = !self.sim.survivor_mode;
         }
 
-        // cull enemies
-        let mut idx = self.enemy_pos.len() as i32 - 1;
-        while idx > 0 {
-            if self.enemy_pos[idx as usize].dist(self.player_pos) > 4.05 {
-                self.enemy_pos.swap_remove(idx as usize);
-                self.enemy_hp.swap_remove(idx as usize);
-                self.enemy_scrap.swap_remove(idx as usize);
-                self.enemy_vel.swap_remove(idx as usize);
+        // right click on a building: shift to demolish, otherwise repair it if its damaged or upgrade it if not
+        if inputs.rmb == KeyStatus::JustPressed {
+            if let Some(idx) = self.sim.building_at(building_cell(mouse_pos)) {
+                self.pending_building_action = Some(if inputs.pressed(VirtualKeyCode::LShift) {
+                    BuildingAction::Demolish
+                } else if self.sim.building_hp[idx] < self.sim.building_def(idx).hp {
+                    BuildingAction::Repair
+                } else {
+                    BuildingAction::Upgrade
+                });
             }
-            idx -= 1;
         }
 
-        // spawn enemies
-        println!("there  are {} enemies", self.enemy_pos.len());
-        let mut seed = inputs.seed;
-
-        while self.enemy_pos.len() < enemy_count {
-            self.enemy_pos.push(self.player_pos.offset_r_theta(4.0, kuniform(seed, 0., 2. * PI)));
-            self.enemy_hp.push(1.0);
-            self.enemy_scrap.push(0);
-            self.enemy_vel.push(Vec2::new(0.0, 0.0));
-            seed = khash(seed);
-        }
-
-        let old_enemy_positions = self.enemy_pos.clone();
-
-        
-        // enemy steering
-        // yea this will need some work, will = 0 it will still keep its velocity
-        // have a think about enemy behaviour
-        for i in 0..self.enemy_pos.len() {
-            let current_dir = self.enemy_vel[i].normalize();
-            let steer_dir = if self.player_pos.dist(self.enemy_pos[i]) > enemy_acquisition_range {
-                Vec2::new(0.0, 0.0)
-            } else {
-                (self.player_pos - self.enemy_pos[i]).normalize()
-            };
-            let new_dir = current_dir.lerp(steer_dir, enemy_steer_amount * inputs.dt as f32).normalize();
-            self.enemy_vel[i] = new_dir * enemy_speed;
+        // quick save / load. F9 gets recorded so a replay with a quickload in it refuses to play
+        if inputs.just_pressed(VirtualKeyCode::F5) {
+            if let Err(e) = save_game(QUICKSAVE_PATH, self.zoom_level, &self.sim) {
+                println!("quicksave failed: {}", e);
+            }
         }
-
-        // enemy movement
-        for i in 0..self.enemy_pos.len() {
-            self.enemy_pos[i] = self.enemy_pos[i] + self.enemy_vel[i] * inputs.dt as f32;
+        // not once dead though, the run is over. the profile remembers which runs its banked
+        // so loading back into one from a later run doesnt bank it twice either
+        if inputs.just_pressed(VirtualKeyCode::F9) && !self.sim.player_dead() {
+            match load_game(QUICKSAVE_PATH) {
+                Ok((zoom_level, sim)) => {
+                    self.zoom_level = zoom_level;
+                    self.sim = sim;
+                    self.sim_accumulator = 0.0;
+                    self.game_over_t = 0.0;
+                    self.banked = false;
+                    self.player_place_building_kind = None;
+                },
+                Err(e) => println!("quickload failed: {}", e),
+            }
         }
 
-        // calculate enemy collisions
-        let mut enemy_collisions:Vec<(usize, usize, Vec2)> = Vec::new();
-        for i in 0..self.enemy_pos.len() {
-            for j in 0..self.enemy_pos.len() {
-                if i == j {continue};
-                let penetration = 2.0 * enemy_radius - (self.enemy_pos[i] - self.enemy_pos[j]).magnitude();
-                if penetration > 0.0 {
-                    let pvec = penetration *  (self.enemy_pos[i] - self.enemy_pos[j]).normalize();
-                    enemy_collisions.push((i, j, pvec));                    
+        // bank some scrap, spend it on the upgrade tree, then click anywhere else to go again in a new world
+        if self.sim.player_dead() {
+            if !self.banked {
+                self.banked = true;
+                if let Some(profile) = self.profile.as_mut() {
+                    profile.bank(&self.sim);
+                }
+                self.save_profile();
+            }
+            self.game_over_t += inputs.dt;
+            if self.game_over_t > GAME_OVER_DELAY && inputs.lmb == KeyStatus::JustPressed {
+                let clicked_node = (0..META_UPGRADES.len()).find(|i| meta_node_rect(inputs.screen_rect, *i).contains(inputs.mouse_pos));
+                match (self.profile.as_mut(), clicked_node) {
+                    (Some(profile), Some(i)) => {
+                        if profile.buy(i) {
+                            self.save_profile();
+                        }
+                    },
+                    _ => {
+                        let survivor_mode = self.sim.survivor_mode;
+                        self.sim = self.new_sim(inputs.seed);
+                        self.sim.survivor_mode = survivor_mode;
+                        self.sim_accumulator = 0.0;
+                        self.game_over_t = 0.0;
+                        self.banked = false;
+                        self.player_place_building_kind = None;
+                    },
                 }
             }
         }
 
-        // apply enemy collisions
-        for (subject, object, pen) in enemy_collisions {
-            self.enemy_pos[subject] = self.enemy_pos[subject] + 0.5 * pen;
-        }
+        let mut commands = self.commands(inputs, mouse_pos);
 
+        // fixed timestep, render interpolates between the last two steps so it lags by up to one step
+        if !self.paused {
+            self.sim_accumulator = (self.sim_accumulator + inputs.dt).min(MAX_FRAME_DT);
+        }
+        while self.sim_accumulator >= SIM_DT as f64 {
+            self.sim.step(&commands, SIM_DT);
+            self.sim_accumulator -= SIM_DT as f64;
+            // one shot things only go to the first step
+            commands.building_action = None;
+            commands.choose_upgrade = None;
+            self.pending_building_action = None;
+            self.pending_upgrade_choice = None;
+        }
 
-        // and spawn a certain distance and roam
-        // enemies: i guess cull a certain distamce from player
-        // and drop scrap
+        self.init = false;
+    }
 
-        // and player needs weapons 
+    pub fn sim(&self) -> &Simulation {
+        &self.sim
+    }
 
-        // player melee enemies
-        let melee_attack = inputs.lmb == KeyStatus::Pressed && self.player_place_building_kind == None && self.t > self.player_next_t;
+    fn alpha(&self) -> f32 {
+        (self.sim_accumulator / SIM_DT as f64) as f32
+    }
 
-        if melee_attack {
-            self.player_next_t = self.t + player_melee_cooldown;
-            self.player_draw_arc_t = self.t + arc_duration;
-            for idx in 0..self.enemy_pos.len() {
-                // circle collision and satisfy dot product for angle
-                let v_aim = mouse_pos - self.player_pos;
-                let v_enemy = self.enemy_pos[idx] - self.player_pos;
-                let theta = v_aim.normalize().dot(v_enemy.normalize()).acos();
+    // camera follows the interpolated player, leaning towards the mouse. returns mouse in world space too
+    fn camera(&self, inputs: &FrameInputState) -> (Rect, Vec2) {
+        let cam_w = self.zoom_level * inputs.screen_rect.w/inputs.screen_rect.h;
+        let cam_h = self.zoom_level;
+        let player_pos = self.sim.interp_player_pos(self.alpha());
+        let camera_rect = Rect::new_centered(player_pos.x, player_pos.y, cam_w, cam_h);
+        let mouse_pos = inputs.mouse_pos.transform(inputs.screen_rect, camera_rect);
+        let new_camera_center = camera_rect.centroid().lerp(mouse_pos, 0.2);
+        let camera_rect = Rect::new_centered(new_camera_center.x, new_camera_center.y, cam_w, cam_h);
+        let mouse_pos = inputs.mouse_pos.transform(inputs.screen_rect, camera_rect);
+        (camera_rect, mouse_pos)
+    }
 
-                if self.player_pos.dist(self.enemy_pos[idx]) < (player_melee_radius + enemy_radius) && theta.abs() < player_melee_arc {
-                    self.enemy_hp[idx] -= player_melee_damage;
-                    self.enemy_pos[idx] = self.enemy_pos[idx] + v_enemy.normalize() * 0.03;
-                }
+    fn commands(&self, inputs: &FrameInputState, mouse_pos: Vec2) -> PlayerCommands {
+        let steer = {
+            let mut steer = Vec2::new(0.0, 0.0);
+            if inputs.pressed(VirtualKeyCode::W) {
+                steer.y = (steer.y - 1.0).max(-1.0);
             }
-        }
-
-        if inputs.lmb == KeyStatus::Pressed && self.player_place_building_kind.is_some() {
-            let i = (mouse_pos.x / building_s).floor() as i32;
-            let j = (mouse_pos.y / building_s).floor() as i32;
-            let bk = self.player_place_building_kind.unwrap();
-            if bk == 0 {
-                // wall
-                if self.player_scrap >= 3 { // check if already occupied too
-                    self.player_scrap -= 3;
-                    self.building_pos.push((i, j));
-                    self.building_kind.push(bk);
-                    self.building_hp.push(4.0);
-                    self.building_next_t.push(0.);
-                }
+            if inputs.pressed(VirtualKeyCode::S) {
+                steer.y = (steer.y + 1.0).min(1.0);
             }
-        }
-
-        // flesh out all the collision types
-
-        // kill enemies with < 0 hp and drop pickups
-        let mut idx = self.enemy_pos.len() - 1;
-        while idx > 0 {
-            if self.enemy_hp[idx] <= 0.0 {
-                self.scrap_pos.push(self.enemy_pos[idx]);
-                self.scrap_vel.push(Vec2::new(0.0, 0.0));
-
-                self.enemy_pos.swap_remove(idx);
-                self.enemy_hp.swap_remove(idx);
-                self.enemy_scrap.swap_remove(idx);
-                self.enemy_vel.swap_remove(idx);
+            if inputs.pressed(VirtualKeyCode::A) {
+                steer.x = (steer.x -  1.0).max(-1.0);
             }
-            idx -= 1;
-        }
-
-        // enemies collide with walls
-        for i in 0..self.building_pos.len() {
-            let building_rect = Rect::new(self.building_pos[i].0 as f32 * building_s, self.building_pos[i].1 as f32 * building_s, building_s, building_s);
-            for j in 0..self.enemy_pos.len() {
-                let closest_point = building_rect.snap(self.enemy_pos[j]);
-                let penetration = enemy_radius - (closest_point - self.enemy_pos[j]).magnitude();
-                if penetration > 0.0 {
-                    let pen_vec = penetration * (closest_point - self.enemy_pos[j]).normalize();
-                    self.enemy_pos[j] = self.enemy_pos[j] - pen_vec;
-                }
+            if inputs.pressed(VirtualKeyCode::D) {
+                steer.x = (steer.x + 1.0).min(1.0);
             }
-        }
+            steer.normalize()
+        };
 
-        // velocity fix
-        for i in 0..self.enemy_pos.len() {
-            self.enemy_vel[i] = (self.enemy_pos[i] - old_enemy_positions[i]) / inputs.dt as f32;
+        PlayerCommands {
+            steer,
+            aim: mouse_pos,
+            attack: inputs.lmb == KeyStatus::Pressed,
+            weapon: self.player_weapon,
+            choose_upgrade: self.pending_upgrade_choice,
+            place_building: self.player_place_building_kind,
+            building_action: self.pending_building_action,
         }
+    }
 
+    // only reads the simulation, all the state changes happen in step
+    fn draw(&self, inputs: &FrameInputState, camera_rect: Rect, mouse_pos: Vec2, alpha: f32, kc: &mut KRCanvas) {
+        let sim = &self.sim;
+        let player_pos = sim.interp_player_pos(alpha);
 
         kc.set_colour(Vec4::new(0.2, 0.6, 0.2, 1.0));
         kc.set_depth(1.0);
         kc.rect(inputs.screen_rect);
 
         kc.set_camera(camera_rect);
-        
-        kc.set_colour(Vec4::new(0.6, 0.0, 0.0, 1.0));
+
+        let (held, held_dir) = if sim.survivor_mode {
+            (sim.player_attack_weapon, sim.player_attack_dir)
+        } else {
+            (self.player_weapon, (mouse_pos - player_pos).normalize())
+        };
+
+        // flashes while its invulnerable, sprites get tinted instead of coloured
+        let flash = sim.t < sim.player_hit_t && (sim.t * 20.0) as i32 % 2 == 0;
+        let player_sprite = self.sprites.get("player").is_some() || self.sprites.animation("player_walk").is_some();
+        kc.set_colour(if sim.player_dead() {
+            Vec4::new(0.3, 0.3, 0.3, 1.0)
+        } else if flash {
+            Vec4::new(1.0, 0.6, 0.6, 1.0)
+        } else if player_sprite {
+            Vec4::new(1.0, 1.0, 1.0, 1.0)
+        } else {
+            Vec4::new(0.6, 0.0, 0.0, 1.0)
+        });
         kc.set_depth(1.5);
-        kc.circle(self.player_pos, player_radius);
+        let player_rect = Rect::new_centered(player_pos.x, player_pos.y, 2.0 * PLAYER_RADIUS, 2.0 * PLAYER_RADIUS);
+        let walking = sim.player_pos.dist(sim.player_prev_pos) > 0.0001;
+        let drawn = (walking && self.sprites.draw_animation(kc, "player_walk", sim.t, player_rect, held_dir.x < 0.0, 0.0))
+            || self.sprites.draw(kc, "player", player_rect, held_dir.x < 0.0, 0.0);
+        if !drawn {
+            kc.circle(player_pos, PLAYER_RADIUS);
+        }
 
-        // render melee arc
-        if self.t < self.player_draw_arc_t {
+        // render the weapon, guns get a barrel pointing at the mouse. in survivor mode its whatever went off last
+        draw_weapon(kc, player_pos, held_dir, PLAYER_RADIUS, held, 1.55);
+
+        // render melee arc or muzzle flash
+        if sim.t < sim.player_draw_arc_t {
+            let weapon = &WEAPON_DEFS[sim.player_attack_weapon as usize];
+            let dir = sim.player_attack_dir;
             kc.set_depth(1.4);
-            let arc_alpha = (self.player_draw_arc_t - self.t)/arc_duration;
+            let arc_alpha = (sim.player_draw_arc_t - sim.t)/ARC_DURATION;
             kc.set_colour(Vec4::new(1.0, 1.0, 1.0, arc_alpha));
 
             // for collision dot product of player facing and bearing to enemy
 
-            let dfacing = (mouse_pos - self.player_pos);
-            let facing_angle = dfacing.y.atan2(dfacing.x);// probably atan2 or something
-            kc.poly_part(self.player_pos, player_melee_radius, facing_angle - PI/4., facing_angle + PI/4., 20);
+            let facing_angle = dir.y.atan2(dir.x);// probably atan2 or something
+            if weapon.projectile.is_none() {
+                kc.poly_part(player_pos, weapon.range, facing_angle - weapon.arc, facing_angle + weapon.arc, 20);
+            } else if sim.player_attack_weapon != WEAPON_GRENADE {
+                let muzzle = player_pos + dir * (PLAYER_RADIUS + 0.04);
+                let since = sim.t - (sim.player_draw_arc_t - ARC_DURATION);
+                kc.set_depth(1.6);
+                kc.set_colour(Vec4::new(1.0, 1.0, 1.0, 1.0));
+                if !self.sprites.draw_animation(kc, "muzzle_flash", since, Rect::new_centered(muzzle.x, muzzle.y, 0.05, 0.05), false, facing_angle) {
+                    kc.set_colour(Vec4::new(1.0, 0.9, 0.5, arc_alpha));
+                    kc.circle(muzzle, 0.025);
+                }
+            }
+        }
+
+        // render explosions, a flash that grows out to the blast radius and fades
+        for (p, t, r) in sim.explosions.iter() {
+            let age = (sim.t - t) / EXPLOSION_DURATION;
+            let size = r * (0.5 + 0.5 * age);
+            kc.set_depth(1.85);
+            kc.set_colour(Vec4::new(1.0, 1.0, 1.0, 1.0 - age));
+            if !self.sprites.draw(kc, "explosion", Rect::new_centered(p.x, p.y, 2.0 * size, 2.0 * size), false, 0.0) {
+                kc.set_colour(Vec4::new(1.0, 0.6 + 0.4 * (1.0 - age), 0.2, 0.8 * (1.0 - age)));
+                kc.circle(*p, size);
+            }
+        }
+
+        // render projectiles, a dot with a streak behind it
+        for i in 0..sim.projectile_pos.len() {
+            let def = &PROJECTILE_DEFS[sim.projectile_kind[i] as usize];
+            let p = sim.interp_projectile_pos(i, alpha);
+            let tail = p - sim.projectile_vel[i] * 0.02;
+            let side = sim.projectile_vel[i].normalize().rotate(PI/2.) * def.radius;
+            let colour = def.colour;
+            kc.set_depth(1.55);
+            kc.set_colour(colour);
+            kc.circle(p, def.radius);
+            kc.set_colour(Vec4::new(colour.x, colour.y, colour.z, 0.4 * colour.w));
+            kc.triangle(p + side, p - side, tail);
         }
 
         // render pickups
         // maybe make it so the actual pickup is the pickup and not the shadow
-        for p in self.scrap_pos.iter() {
+        for i in 0..sim.scrap_pos.len() {
+            let p = &sim.interp_scrap_pos(i, alpha);
             kc.set_depth(1.1);
             kc.set_colour(Vec4::new(0.0, 0.0, 0.0, 0.4));
             kc.circle(*p, 0.02);
             kc.set_depth(1.5);
-            kc.set_colour(Vec4::new(0.0, 0.0, 1.0, 1.0));
-            kc.circle(*p + Vec2::new(0.0, -0.05 + (self.t * 3.0).sin() * 0.015), 0.02);
+            let bob = *p + Vec2::new(0.0, -0.05 + (sim.t * 3.0).sin() * 0.015);
+            kc.set_colour(Vec4::new(1.0, 1.0, 1.0, 1.0));
+            // the animation does its own bobbing
+            let lifted = *p + Vec2::new(0.0, -0.05);
+            if !self.sprites.draw_animation(kc, "scrap_bob", sim.t, Rect::new_centered(lifted.x, lifted.y, 0.04, 0.04), false, 0.0)
+                    && !self.sprites.draw(kc, "scrap", Rect::new_centered(bob.x, bob.y, 0.04, 0.04), false, 0.0) {
+                kc.set_colour(Vec4::new(0.0, 0.0, 1.0, 1.0));
+                kc.circle(bob, 0.02);
+            }
         }
 
         // render enemies
-        for idx in 0..self.enemy_pos.len() {
-            kc.set_depth(1.5);
-            kc.set_colour(Vec4::new(0.0, 0.0, 0.0, 1.0));
-            kc.circle(self.enemy_pos[idx], enemy_radius + enemy_radius_per_scrap * self.enemy_scrap[idx] as f32);
+        for idx in 0..sim.enemy_pos.len() {
+            // ghosts go through walls so draw them over the top
+            let ghost = sim.enemy_def(idx).behaviour == EnemyBehaviour::Haunt;
+            kc.set_depth(if ghost {1.95} else {1.5});
+            let p = sim.interp_enemy_pos(idx, alpha);
+            let r = sim.enemy_radius(idx);
+            let colour = sim.enemy_def(idx).colour;
+            kc.set_colour(Vec4::new(1.0, 1.0, 1.0, colour.w));
+            let facing_left = sim.enemy_vel[idx].x < 0.0;
+            let name = enemy_sprite(sim.enemy_kind[idx]);
+            let enemy_rect = Rect::new_centered(p.x, p.y, 2.0 * r, 2.0 * r);
+            // out of step with each other so a crowd doesnt march
+            let walk_t = sim.t + (idx % 7) as f32 * 0.13;
+            if !self.sprites.draw_animation(kc, &format!("{}_walk", name), walk_t, enemy_rect, facing_left, 0.0)
+                    && !self.sprites.draw(kc, name, enemy_rect, facing_left, 0.0) {
+                kc.set_colour(colour);
+                kc.circle(p, r);
+            }
         }
 
-        // render buildings
-        for i in 0..self.building_pos.len() {
-            let building_rect = Rect::new(self.building_pos[i].0 as f32 * building_s, self.building_pos[i].1 as f32 * building_s, building_s, building_s);
-            if self.building_kind[i] == 0 {
-                // wall
-                kc.set_depth(1.5);
-                kc.set_colour(Vec4::new(0.5, 0.5, 0.5, 1.0));
-                kc.rect(building_rect);
-                kc.set_depth(1.6);
-                kc.set_colour(Vec4::new(0.3, 0.3, 0.3, 1.0));
-                kc.rect(building_rect.dilate_pc(-0.05));
+        // render buildings, turrets point at what theyd shoot so they need the hash, only built if theres one
+        let has_turret = (0..sim.building_pos.len()).any(|i| sim.building_def(i).base == BUILDING_TURRET);
+        let enemy_hash = if has_turret {Some(SpatialHash::build(&sim.enemy_pos, ENEMY_HASH_CELL))} else {None};
+        for i in 0..sim.building_pos.len() {
+            let building_rect = sim.building_rect(i);
+            let target = match &enemy_hash {
+                Some(hash) if sim.building_def(i).base == BUILDING_TURRET => sim.turret_target(i, hash),
+                _ => None,
+            };
+            let facing = match target {
+                Some(e) => sim.enemy_pos[e] - building_rect.centroid(),
+                None => Vec2::new(0.0, -1.0),
+            };
+            let cooldown = sim.building_def(i).cooldown;
+            // only generators and turrets charge, walls have no cooldown
+            let charge = if cooldown > 0.0 {1.0 - ((sim.building_next_t[i] - sim.t) / cooldown).clamp(0.0, 1.0)} else {0.0};
+            let health = sim.building_hp[i] / sim.building_def(i).hp;
+            let kind = sim.building_kind[i];
+            kc.set_depth(1.5);
+            kc.set_colour(Vec4::new(1.0, 1.0, 1.0, 1.0));
+            // damage states are an animation that goes from full health to wrecked, otherwise cracks over the top
+            let name = format!("{}_{}", building_name(kind), BUILDING_DEFS[kind as usize].tier);
+            if self.sprites.draw_animation_progress(kc, &format!("{}_damage", name), 1.0 - health, building_rect, false, 0.0) {
+                continue;
             }
+            if !self.sprites.draw(kc, &name, building_rect, false, 0.0) {
+                draw_building(kc, building_rect, kind, facing, charge, 1.0);
+            }
+            draw_damage(kc, building_rect, health, khash(sim.building_pos[i].0 as u32 ^ khash(sim.building_pos[i].1 as u32)));
         }
-        
+
         // render hover building
         if let Some(bk) = self.player_place_building_kind {
-            let i = (mouse_pos.x / building_s).floor() as i32;
-            let j = (mouse_pos.y / building_s).floor() as i32;
-            let building_rect = Rect::new(i as f32 * building_s, j as f32 * building_s, building_s, building_s);
-            if bk == 0 {
-                // wall
-                kc.set_depth(1.5);
-                kc.set_colour(Vec4::new(0.5, 0.5, 0.5, 0.5));
+            let cell = building_cell(mouse_pos);
+            let building_rect = cell_rect(cell);
+            draw_building(kc, building_rect, bk, Vec2::new(0.0, -1.0), 0.0, 0.5);
+            if !sim.can_place_building(cell, bk) {
+                kc.set_depth(1.9);
+                kc.set_colour(Vec4::new(1.0, 0.0, 0.0, 0.4));
                 kc.rect(building_rect);
-                kc.set_depth(1.6);
-                kc.set_colour(Vec4::new(0.3, 0.3, 0.3, 0.5));
-                kc.rect(building_rect.dilate_pc(-0.05));
             }
         }
 
@@ -432,9 +453,9 @@ impl Game {
             while grass_y < camera_rect.bot() + 0.1 {
                 let site_seed = seed_grid(69, grass_x, grass_y, grass_spacing);
                 if chance(site_seed, 0.2) {
-                    let xo = kuniform(site_seed * 1231513, -grass_max_offset, grass_max_offset);
-                    let yo = kuniform(site_seed * 1238987, -grass_max_offset, grass_max_offset);
-                    
+                    let xo = kuniform(site_seed.wrapping_mul(1231513), -grass_max_offset, grass_max_offset);
+                    let yo = kuniform(site_seed.wrapping_mul(1238987), -grass_max_offset, grass_max_offset);
+
                     // just draw rect for now but i will draw a shadow layer then a grass layer
                     // what does a grass look like
                     kc.set_depth(1.1);
@@ -455,13 +476,437 @@ impl Game {
             }
             grass_x += grass_spacing;
         }
-        
+
         kc.set_camera(inputs.screen_rect);
         kc.set_depth(10.0);
-        let darkness = (day_t * 2.0 * PI).sin().max(0.0).min(0.8);
+        let darkness = (sim.day_t() * 2.0 * PI).sin().clamp(0.0, 0.8);
         kc.set_colour(Vec4::new(0.0, 0.0, 0.0, darkness));
         kc.rect(inputs.screen_rect);
 
-        self.init = false;
+        draw_hud(kc, inputs.screen_rect, sim, self.player_place_building_kind);
+        if sim.survivor_mode {
+            draw_xp_bar(kc, inputs.screen_rect, sim);
+        }
+        if !sim.level_up_choices.is_empty() {
+            draw_level_up(kc, inputs.screen_rect, inputs.mouse_pos, &sim.level_up_choices);
+        }
+        if sim.player_dead() {
+            self.draw_game_over(inputs.screen_rect, kc);
+        }
+    }
+
+    // how long they lasted, kills and scrap, each next to a little picture of what it is
+    fn draw_game_over(&self, screen_rect: Rect, kc: &mut KRCanvas) {
+        let sim = &self.sim;
+        kc.set_depth(11.0);
+        kc.set_colour(Vec4::new(0.2, 0.0, 0.0, 0.7));
+        kc.rect(screen_rect);
+
+        let h = 0.08;
+        let c = screen_rect.centroid();
+        let secs = sim.t as i32;
+        kc.set_depth(11.5);
+        kc.set_colour(Vec4::new(1.0, 1.0, 1.0, 1.0));
+        kc.set_text_align(TextAlign::Centre);
+        kc.text(Vec2::new(c.x, c.y - 3.4 * h), h, "you died");
+        kc.set_text_align(TextAlign::Left);
+
+        let rows = [
+            format!("{}:{:02}", secs / 60, secs % 60),
+            format!("{}", sim.kills),
+            format!("{}", sim.scrap_collected),
+        ];
+        // icons and numbers centred together on the widest row
+        let w = rows.iter().map(|row| KRCanvas::measure_text(h, row).x).fold(0.0, f32::max);
+        let x = c.x - (1.2 * h + w) / 2.0 + 1.2 * h;
+        for (i, row) in rows.iter().enumerate() {
+            let y = c.y - 1.8 * h + i as f32 * 1.4 * h;
+            let icon = Vec2::new(x - 0.6 * h, y + 0.5 * h);
+            kc.set_depth(11.5);
+            match i {
+                0 => draw_clock(kc, icon, 0.4 * h),
+                1 => {
+                    kc.set_colour(ENEMY_DEFS[ENEMY_GRUNT as usize].colour);
+                    kc.circle(icon, 0.3 * h);
+                },
+                _ => {
+                    kc.set_colour(Vec4::new(0.0, 0.0, 1.0, 1.0));
+                    kc.circle(icon, 0.25 * h);
+                },
+            }
+            kc.set_colour(Vec4::new(1.0, 1.0, 1.0, 1.0));
+            kc.text(Vec2::new(x, y), h, row);
+        }
+
+        // ready to go again
+        if self.game_over_t > GAME_OVER_DELAY {
+            let pulse = 0.5 + 0.5 * (self.game_over_t as f32 * 4.0).sin();
+            kc.set_colour(Vec4::new(1.0, 1.0, 1.0, 0.3 + 0.5 * pulse));
+            kc.set_text_align(TextAlign::Centre);
+            kc.text(Vec2::new(c.x, c.y + 2.4 * h), 0.4 * h, "click to go again");
+            kc.set_text_align(TextAlign::Left);
+        }
+
+        if let Some(profile) = &self.profile {
+            draw_upgrade_tree(kc, screen_rect, profile);
+        }
+    }
+}
+
+// where each meta upgrade sits in the tree, (column, row), things sit under what they need
+const META_NODE_CELLS: [(i32, i32); 4] = [(0, 0), (1, 0), (2, 0), (2, 1)];
+
+fn meta_node_rect(screen_rect: Rect, i: usize) -> Rect {
+    let s = 0.07;
+    let gap = 0.03;
+    let total = 3.0 * s + 2.0 * gap;
+    let c = screen_rect.centroid();
+    let (col, row) = META_NODE_CELLS[i];
+    Rect::new(c.x - total/2. + col as f32 * (s + gap), c.y + 0.24 + row as f32 * (s + 0.06), s, s)
+}
+
+// banked scrap next to it, each node with pips for its levels and what the next one costs
+fn draw_upgrade_tree(kc: &mut KRCanvas, screen_rect: Rect, profile: &Profile) {
+    let banked_at = meta_node_rect(screen_rect, META_TURRET);
+    let icon = Vec2::new(banked_at.right() + 0.06, banked_at.centroid().y);
+    kc.set_depth(11.5);
+    kc.set_colour(Vec4::new(0.0, 0.0, 1.0, 1.0));
+    kc.circle(icon, 0.02);
+    kc.set_colour(Vec4::new(1.0, 1.0, 1.0, 1.0));
+    kc.text(Vec2::new(icon.x + 0.04, icon.y - 0.02), 0.04, &format!("{}", profile.banked_scrap));
+
+    for (i, def) in META_UPGRADES.iter().enumerate() {
+        let r = meta_node_rect(screen_rect, i);
+        if let Some(req) = def.requires {
+            let from = meta_node_rect(screen_rect, req).centroid();
+            let to = r.centroid();
+            kc.set_depth(11.2);
+            kc.set_colour(Vec4::new(0.6, 0.6, 0.6, 1.0));
+            kc.rect(Rect::new(from.x - 0.003, from.y, 0.006, to.y - from.y));
+        }
+
+        kc.set_depth(11.3);
+        kc.set_colour(if profile.cost(i).is_none() {
+            Vec4::new(0.2, 0.5, 0.2, 1.0)
+        } else if profile.can_buy(i) {
+            Vec4::new(0.5, 0.5, 0.5, 1.0)
+        } else if profile.available(i) {
+            Vec4::new(0.25, 0.25, 0.25, 1.0)
+        } else {
+            Vec4::new(0.1, 0.1, 0.1, 1.0)
+        });
+        kc.rect(r);
+        draw_meta_icon(kc, r.centroid(), r.w * 0.35, i);
+
+        let pip = 0.01;
+        for l in 0..def.max_level {
+            kc.set_depth(11.5);
+            kc.set_colour(if l < profile.level(i) {Vec4::new(1.0, 0.9, 0.3, 1.0)} else {Vec4::new(0.3, 0.3, 0.3, 1.0)});
+            kc.rect(Rect::new(r.x + l as f32 * 1.5 * pip, r.bot() + 0.005, pip, pip));
+        }
+        if let Some(cost) = profile.cost(i) {
+            kc.set_colour(Vec4::new(1.0, 1.0, 1.0, 1.0));
+            kc.text(Vec2::new(r.x, r.bot() + 0.02), 0.02, &format!("{}", cost));
+        }
+    }
+}
+
+fn draw_meta_icon(kc: &mut KRCanvas, c: Vec2, r: f32, upgrade: usize) {
+    kc.set_depth(11.5);
+    match upgrade {
+        META_START_HP => {
+            kc.set_colour(Vec4::new(0.9, 0.1, 0.1, 1.0));
+            kc.rect(Rect::new(c.x - 0.3 * r, c.y - r, 0.6 * r, 2.0 * r));
+            kc.rect(Rect::new(c.x - r, c.y - 0.3 * r, 2.0 * r, 0.6 * r));
+        },
+        META_PICKUP_RADIUS => draw_upgrade_icon(kc, c, r, Upgrade::PickupRadius),
+        META_TURRET => {
+            kc.set_colour(Vec4::new(0.35, 0.35, 0.45, 1.0));
+            kc.rect(Rect::new(c.x - r, c.y - r, 2.0 * r, 2.0 * r));
+            kc.set_depth(11.6);
+            kc.set_colour(Vec4::new(0.15, 0.15, 0.2, 1.0));
+            kc.circle(c, 0.4 * r);
+            kc.rect(Rect::new(c.x, c.y - 0.12 * r, r, 0.24 * r));
+        },
+        _ => {
+            kc.set_colour(Vec4::new(0.55, 0.35, 0.2, 1.0));
+            kc.rect(Rect::new(c.x - r, c.y - r, 2.0 * r, 2.0 * r));
+            kc.set_depth(11.6);
+            kc.set_colour(Vec4::new(1.0, 0.8, 0.2, 1.0));
+            kc.circle(c, 0.4 * r);
+        },
+    }
+}
+
+// stick for melee, a barrel for guns that gets fatter the more it shoots at once, or a lump of scrap to throw
+fn draw_weapon(kc: &mut KRCanvas, pos: Vec2, dir: Vec2, r: f32, weapon: i32, depth: f32) {
+    let def = &WEAPON_DEFS[weapon as usize];
+    let muzzle = pos + dir * (r + 0.04);
+    let base = pos + dir * r * 0.5;
+    kc.set_depth(depth);
+    if weapon == WEAPON_GRENADE {
+        kc.set_colour(Vec4::new(0.0, 0.0, 1.0, 1.0));
+        kc.circle(pos + dir * r, 0.02);
+        return;
+    }
+    let side = match def.projectile {
+        Some(_) => dir.rotate(PI/2.) * (0.008 + 0.004 * def.count.min(3) as f32),
+        None => dir.rotate(PI/2.) * 0.005,
+    };
+    kc.set_colour(match def.projectile {
+        Some(_) => Vec4::new(0.15, 0.15, 0.2, 1.0),
+        None => Vec4::new(0.4, 0.25, 0.1, 1.0),
+    });
+    kc.triangle(base + side, base - side, muzzle + side);
+    kc.triangle(base - side, muzzle - side, muzzle + side);
+}
+
+fn draw_clock(kc: &mut KRCanvas, c: Vec2, r: f32) {
+    kc.set_colour(Vec4::new(1.0, 1.0, 1.0, 1.0));
+    kc.circle(c, r);
+    kc.set_depth(11.6);
+    kc.set_colour(Vec4::new(0.0, 0.0, 0.0, 1.0));
+    kc.rect(Rect::new(c.x - 0.075 * r, c.y - 0.75 * r, 0.15 * r, 0.75 * r));
+    kc.rect(Rect::new(c.x, c.y - 0.075 * r, 0.5 * r, 0.15 * r));
+}
+
+// along the top of the screen, fills up towards the next level
+fn draw_xp_bar(kc: &mut KRCanvas, screen_rect: Rect, sim: &Simulation) {
+    let bar = Rect::new(screen_rect.x + 0.02, screen_rect.y + 0.01, screen_rect.w - 0.04, 0.015);
+    let fill = sim.player_xp as f32 / xp_to_level(sim.player_level) as f32;
+    kc.set_depth(10.5);
+    kc.bar(bar, fill, Vec4::new(0.0, 0.0, 0.0, 0.6), Vec4::new(0.2, 0.4, 1.0, 1.0));
+}
+
+// what the sprite for each kind is called in atlas.json
+fn enemy_sprite(kind: i32) -> &'static str {
+    match kind {
+        ENEMY_GRUNT => "enemy_grunt",
+        ENEMY_RUNNER => "enemy_runner",
+        ENEMY_BRUTE => "enemy_brute",
+        ENEMY_SPITTER => "enemy_spitter",
+        ENEMY_WRECKER => "enemy_wrecker",
+        _ => "enemy_ghost",
+    }
+}
+
+fn building_name(kind: i32) -> &'static str {
+    match BUILDING_DEFS[kind as usize].base {
+        BUILDING_WALL => "wall",
+        BUILDING_TURRET => "turret",
+        _ => "generator",
+    }
+}
+
+// hp and melee cooldown bars with scrap under them in the top left, the clock in the top right
+// and what can be built down the bottom left
+fn draw_hud(kc: &mut KRCanvas, screen_rect: Rect, sim: &Simulation, place_kind: Option<i32>) {
+    let size = 0.03;
+    let x = screen_rect.x + 0.02;
+    let y = screen_rect.y + 0.04;
+    let white = Vec4::new(1.0, 1.0, 1.0, 1.0);
+    let back = Vec4::new(0.0, 0.0, 0.0, 0.6);
+    kc.set_depth(10.5);
+    kc.set_text_align(TextAlign::Left);
+
+    // bars start just past the longest label
+    let bar_x = x + KRCanvas::measure_text(size, "melee").x + 0.02;
+
+    kc.set_colour(white);
+    kc.text(Vec2::new(x, y), size, "hp");
+    kc.bar(Rect::new(bar_x, y + 0.1 * size, 0.2, 0.7 * size), sim.player_hp / sim.player_stats.max_hp, back, Vec4::new(0.8, 0.1, 0.1, 1.0));
+
+    let cooldown = WEAPON_DEFS[WEAPON_MELEE as usize].cooldown * sim.player_stats.cooldown;
+    let ready = 1.0 - (sim.player_weapon_next_t[WEAPON_MELEE as usize] - sim.t) / cooldown;
+    kc.set_colour(white);
+    kc.text(Vec2::new(x, y + 1.3 * size), size, "melee");
+    kc.bar(Rect::new(bar_x, y + 1.4 * size, 0.2, 0.7 * size), ready, back, Vec4::new(0.9, 0.9, 0.9, 1.0));
+
+    kc.set_colour(Vec4::new(0.0, 0.0, 1.0, 1.0));
+    kc.circle(Vec2::new(x + 0.3 * size, y + 3.1 * size), 0.3 * size);
+    kc.set_colour(white);
+    kc.text(Vec2::new(bar_x, y + 2.6 * size), size, &format!("{}", sim.player_scrap));
+
+    // clock goes round once a day, the top half is night and the bottom is day
+    // so the hand starts on the left at nightfall and crosses the right at dawn
+    let c = Vec2::new(screen_rect.right() - 0.06, y + 0.02);
+    let r = 0.035;
+    kc.set_colour(Vec4::new(0.1, 0.1, 0.3, 1.0));
+    kc.poly_part(c, r, PI, 2.*PI, 20);
+    kc.set_colour(Vec4::new(0.9, 0.8, 0.3, 1.0));
+    kc.poly_part(c, r, 0.0, PI, 20);
+    let tip = c.offset_r_theta(r, PI + (sim.day_t() + 1.0) * PI);
+    let side = (tip - c).normalize().rotate(PI/2.) * 0.004;
+    kc.set_depth(10.6);
+    kc.set_colour(white);
+    kc.triangle(c + side, c - side, tip);
+    let label = if sim.day_t() > 0.0 {"day"} else {"night"};
+    kc.set_text_align(TextAlign::Right);
+    kc.text(Vec2::new(c.x - r - 0.015, c.y - size/2.), size, &format!("{} {}", label, (sim.t / 200.0) as i32 + 1));
+    kc.set_text_align(TextAlign::Left);
+
+    // whats buildable, the selected one bright and the cost red if it cant be afforded
+    let buildable: Vec<(i32, &str)> = [(BUILDING_WALL, "Q"), (BUILDING_TURRET, "E"), (BUILDING_GENERATOR, "R")].into_iter()
+        .filter(|(kind, _)| sim.unlocked_buildings.contains(kind))
+        .collect();
+    for (row, (kind, key)) in buildable.iter().enumerate() {
+        let y = screen_rect.bot() - 0.02 - (buildable.len() - row) as f32 * 1.2 * size;
+        let selected = place_kind == Some(*kind);
+        kc.set_colour(if selected {white} else {Vec4::new(0.6, 0.6, 0.6, 0.8)});
+        kc.text(Vec2::new(x, y), size, &format!("{} {}", key, building_name(*kind)));
+        let cost = BUILDING_DEFS[*kind as usize].cost;
+        kc.set_colour(if sim.player_scrap >= cost {Vec4::new(0.5, 0.6, 1.0, 1.0)} else {Vec4::new(1.0, 0.2, 0.2, 1.0)});
+        kc.text(Vec2::new(x + 0.3, y), size, &format!("{}", cost));
+    }
+}
+
+// the level up cards in a row across the middle of the screen
+fn upgrade_card_rect(screen_rect: Rect, i: usize) -> Rect {
+    let w = 0.2;
+    let gap = 0.05;
+    let total = LEVEL_UP_CHOICES as f32 * w + (LEVEL_UP_CHOICES - 1) as f32 * gap;
+    let c = screen_rect.centroid();
+    Rect::new(c.x - total/2. + i as f32 * (w + gap), c.y - 0.15, w, 0.3)
+}
+
+fn draw_level_up(kc: &mut KRCanvas, screen_rect: Rect, mouse_pos: Vec2, choices: &[Upgrade]) {
+    kc.set_depth(11.0);
+    kc.set_colour(Vec4::new(0.0, 0.0, 0.1, 0.6));
+    kc.rect(screen_rect);
+    for (i, upgrade) in choices.iter().enumerate() {
+        let card = upgrade_card_rect(screen_rect, i);
+        let hover = card.contains(mouse_pos);
+        kc.set_depth(11.2);
+        kc.set_colour(if hover {Vec4::new(0.45, 0.45, 0.55, 1.0)} else {Vec4::new(0.3, 0.3, 0.35, 1.0)});
+        kc.rect(card);
+        draw_upgrade_icon(kc, card.centroid(), 0.06, *upgrade);
+        // which key picks it
+        kc.set_depth(11.8);
+        kc.set_colour(Vec4::new(1.0, 1.0, 1.0, 1.0));
+        kc.text(Vec2::new(card.x + 0.01, card.y + 0.01), 0.04, &format!("{}", i + 1));
     }
-}
\ No newline at end of file
+}
+
+fn draw_upgrade_icon(kc: &mut KRCanvas, c: Vec2, r: f32, upgrade: Upgrade) {
+    kc.set_depth(11.5);
+    match upgrade {
+        Upgrade::Damage => {
+            // a blade
+            kc.set_colour(Vec4::new(0.9, 0.2, 0.1, 1.0));
+            kc.triangle(c + Vec2::new(0.0, -r), c + Vec2::new(-0.25 * r, 0.5 * r), c + Vec2::new(0.25 * r, 0.5 * r));
+            kc.rect(Rect::new(c.x - 0.5 * r, c.y + 0.5 * r, r, 0.15 * r));
+            kc.rect(Rect::new(c.x - 0.1 * r, c.y + 0.5 * r, 0.2 * r, 0.5 * r));
+        },
+        Upgrade::Cooldown => draw_clock(kc, c, r),
+        Upgrade::PickupRadius => {
+            kc.set_colour(Vec4::new(0.4, 0.6, 1.0, 0.5));
+            kc.circle(c, r);
+            kc.set_depth(11.6);
+            kc.set_colour(Vec4::new(0.0, 0.0, 1.0, 1.0));
+            kc.circle(c, 0.3 * r);
+        },
+        Upgrade::SuckForce => {
+            // arrows pulling in on a bit of scrap
+            kc.set_colour(Vec4::new(0.0, 0.0, 1.0, 1.0));
+            kc.circle(c, 0.3 * r);
+            kc.set_colour(Vec4::new(1.0, 1.0, 1.0, 1.0));
+            for k in 0..4 {
+                let d = Vec2::new(1.0, 0.0).rotate(k as f32 * PI/2.);
+                let tip = c + d * 0.45 * r;
+                let side = d.rotate(PI/2.) * 0.25 * r;
+                kc.triangle(tip, c + d * r + side, c + d * r - side);
+            }
+        },
+        Upgrade::MoveSpeed => {
+            kc.set_colour(Vec4::new(1.0, 0.9, 0.3, 1.0));
+            for k in 0..2 {
+                let x = c.x - 0.6 * r + k as f32 * 0.7 * r;
+                kc.triangle(Vec2::new(x, c.y - 0.6 * r), Vec2::new(x + 0.5 * r, c.y), Vec2::new(x, c.y + 0.6 * r));
+            }
+        },
+        Upgrade::NewWeapon(w) => {
+            kc.set_colour(Vec4::new(0.6, 0.0, 0.0, 1.0));
+            kc.circle(c - Vec2::new(0.5 * r, 0.0), 0.5 * r);
+            draw_weapon(kc, c - Vec2::new(0.5 * r, 0.0), Vec2::new(1.0, 0.0), r, w, 11.6);
+        },
+    }
+}
+
+// facing is where a turret points, charge is how close a generator is to making scrap
+fn draw_building(kc: &mut KRCanvas, building_rect: Rect, kind: i32, facing: Vec2, charge: f32, alpha: f32) {
+    let def = &BUILDING_DEFS[kind as usize];
+    if def.base == BUILDING_WALL {
+        kc.set_depth(1.5);
+        kc.set_colour(Vec4::new(0.5, 0.5, 0.5, alpha));
+        kc.rect(building_rect);
+        kc.set_depth(1.6);
+        kc.set_colour(Vec4::new(0.3, 0.3, 0.3, alpha));
+        kc.rect(building_rect.dilate_pc(-0.05));
+    } else if def.base == BUILDING_TURRET {
+        // base, then a head with a barrel pointing at whatever its shooting
+        kc.set_depth(1.5);
+        kc.set_colour(Vec4::new(0.5, 0.5, 0.5, alpha));
+        kc.rect(building_rect);
+        kc.set_depth(1.6);
+        kc.set_colour(Vec4::new(0.35, 0.35, 0.45, alpha));
+        kc.rect(building_rect.dilate_pc(-0.15));
+        let c = building_rect.centroid();
+        let along = facing.normalize() * building_rect.w * 0.45;
+        let side = facing.normalize().rotate(PI/2.) * building_rect.w * 0.06;
+        kc.set_depth(1.7);
+        kc.set_colour(Vec4::new(0.15, 0.15, 0.2, alpha));
+        kc.triangle(c + side, c - side, c + along + side);
+        kc.triangle(c - side, c + along - side, c + along + side);
+        kc.circle(c, building_rect.w * 0.2);
+    } else if def.base == BUILDING_GENERATOR {
+        // rusty box with a core that fills up and glows as the next scrap gets closer
+        kc.set_depth(1.5);
+        kc.set_colour(Vec4::new(0.55, 0.35, 0.2, alpha));
+        kc.rect(building_rect);
+        kc.set_depth(1.6);
+        kc.set_colour(Vec4::new(0.35, 0.22, 0.12, alpha));
+        kc.rect(building_rect.dilate_pc(-0.1));
+        let c = building_rect.centroid();
+        kc.set_depth(1.7);
+        kc.set_colour(Vec4::new(0.1, 0.1, 0.1, alpha));
+        kc.circle(c, building_rect.w * 0.25);
+        kc.set_depth(1.8);
+        kc.set_colour(Vec4::new(0.2 + 0.8 * charge, 0.2 + 0.6 * charge, 0.2, alpha));
+        kc.circle(c, building_rect.w * 0.25 * charge);
+    }
+
+    // upgraded ones get bolted on plates in the corners
+    if def.tier >= 2 {
+        let inset = building_rect.dilate_pc(-0.15);
+        kc.set_depth(1.75);
+        kc.set_colour(Vec4::new(0.75, 0.75, 0.7, alpha));
+        for corner in [inset.tl(), inset.tr(), inset.bl(), inset.br()] {
+            kc.circle(corner, building_rect.w * 0.07);
+        }
+    }
+}
+
+// cracks, more of them the more damaged it is. seed keeps them in the same place each frame
+fn draw_damage(kc: &mut KRCanvas, building_rect: Rect, health: f32, seed: u32) {
+    if health >= 1.0 {
+        return;
+    }
+    let c = building_rect.centroid();
+    let n_cracks = ((1.0 - health) * 5.0).ceil() as u32;
+    kc.set_depth(1.9);
+    kc.set_colour(Vec4::new(0.1, 0.05, 0.0, 0.4 + 0.5 * (1.0 - health)));
+    for i in 0..n_cracks {
+        let crack_seed = khash(seed.wrapping_add(i));
+        let theta = kuniform(crack_seed, 0., 2. * PI);
+        let start = building_rect.snap(c.offset_r_theta(building_rect.w, theta));
+        let end = c.offset_r_theta(kuniform(khash(crack_seed), 0.0, 0.2) * building_rect.w, theta + 0.5);
+        let side = (end - start).normalize().rotate(PI/2.) * building_rect.w * 0.03;
+        kc.triangle(start + side, start - side, end);
+    }
+
+    // and a red tint when its nearly gone
+    if health < 0.3 {
+        kc.set_colour(Vec4::new(0.8, 0.1, 0.0, 0.3));
+        kc.rect(building_rect);
+    }
+}
@@ -70,6 +70,25 @@ impl KRenderer {
     }
 }
 
+// The atlas is a grid of ATLAS_CELL pixel cells, sprites are addressed by cell
+const ATLAS_CELL: f32 = 32.0;
+
+// Bitmap font packed into the atlas, ascii ' ' to '~' in a row of 6x8 pixel cells starting at FONT_ORIGIN.
+// Glyphs are 5x7 so the blank column and row give the spacing. src/bin/gen-atlas.rs draws them in.
+const ATLAS_SIZE: f32 = 640.0;
+const FONT_ORIGIN: Vec2 = Vec2 { x: 0.0, y: 512.0 };
+const GLYPH_W: f32 = 6.0;
+const GLYPH_H: f32 = 8.0;
+const FIRST_GLYPH: char = ' ';
+const LAST_GLYPH: char = '~';
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum TextAlign {
+    Left,
+    Centre,
+    Right,
+}
+
 pub struct KRCanvas {
     depth: f32,
     colour: Vec4,
@@ -77,6 +96,7 @@ pub struct KRCanvas {
     uv_clip: Rect,
     uv_from: Rect,
     from_rect: Rect,
+    text_align: TextAlign,
 }
 
 impl KRCanvas {
@@ -88,6 +108,7 @@ impl KRCanvas {
             uv_clip: Rect::new(0.0, 0.0, 1.0/20.0, 1.0/20.0),
             uv_from: Rect::new(-1000.0, -1000.0, 2000.0, 2000.0),
             from_rect: screen_rect,
+            text_align: TextAlign::Left,
         }
     }
     pub fn set_colour(&mut self, c: Vec4) {
@@ -100,43 +121,20 @@ impl KRCanvas {
     pub fn set_camera(&mut self, cam: Rect) {
         self.from_rect = cam;
     }
-    pub fn triangle(&mut self, a: Vec2, b: Vec2, c: Vec2) {
-        self.uv_from = Triangle{a,b,c}.aabb();
-        let write_float_bytes = |buf: &mut Vec<u8>, x: f32| {
-            for b in x.to_le_bytes() {
-                buf.push(b);
-            }
-        };
-        let write_vec2_bytes = |buf: &mut Vec<u8>, v: Vec2| {
-            write_float_bytes(buf, v.x);
-            write_float_bytes(buf, v.y);
-        };
-        let write_vec3_bytes = |buf: &mut Vec<u8>, v: Vec3| {
-            write_float_bytes(buf, v.x);
-            write_float_bytes(buf, v.y);
-            write_float_bytes(buf, v.z);
-        };
-        let write_vec4_bytes = |buf: &mut Vec<u8>, v: Vec4| {
-            write_float_bytes(buf, v.x);
-            write_float_bytes(buf, v.y);
-            write_float_bytes(buf, v.z);
-            write_float_bytes(buf, v.w);
-        };
+    fn vertex(&mut self, p: Vec2, uv: Vec2) {
         // ndc
         let to_rect = Rect::new(0.0, 0.0, 1.0, 1.0);
+        let p = p.transform(self.from_rect, to_rect);
+        for x in [p.x, p.y, self.depth, self.colour.x, self.colour.y, self.colour.z, self.colour.w, uv.x, uv.y] {
+            self.buf.extend_from_slice(&x.to_le_bytes());
+        }
+    }
 
-        // a
-        write_vec3_bytes(&mut self.buf, a.transform(self.from_rect, to_rect).promote(self.depth));
-        write_vec4_bytes(&mut self.buf, self.colour);
-        write_vec2_bytes(&mut self.buf, a.transform(self.uv_from, self.uv_clip));
-        // b
-        write_vec3_bytes(&mut self.buf, b.transform(self.from_rect, to_rect).promote(self.depth));
-        write_vec4_bytes(&mut self.buf, self.colour);
-        write_vec2_bytes(&mut self.buf, b.transform(self.uv_from, self.uv_clip));
-        // c
-        write_vec3_bytes(&mut self.buf, c.transform(self.from_rect, to_rect).promote(self.depth));
-        write_vec4_bytes(&mut self.buf, self.colour);
-        write_vec2_bytes(&mut self.buf, c.transform(self.uv_from, self.uv_clip));
+    pub fn triangle(&mut self, a: Vec2, b: Vec2, c: Vec2) {
+        self.uv_from = Triangle{a,b,c}.aabb();
+        self.vertex(a, a.transform(self.uv_from, self.uv_clip));
+        self.vertex(b, b.transform(self.uv_from, self.uv_clip));
+        self.vertex(c, c.transform(self.uv_from, self.uv_clip));
     }
 
     pub fn rect(&mut self, r: Rect) {
@@ -163,7 +161,102 @@ impl KRCanvas {
     pub fn circle(&mut self, center: Vec2, radius: f32) {
         self.poly(center, radius, 40);
     }
+
+    // one cell of the atlas stretched over dst, tinted by the colour. flip mirrors it left to right,
+    // rotation is about the middle of dst
+    pub fn sprite(&mut self, dst: Rect, atlas_cell: (u32, u32), flip: bool, rotation: f32) {
+        self.sprite_cells(dst, atlas_cell, (1, 1), flip, rotation);
+    }
+
+    // same but for art bigger than a cell, cells is how many across and down from atlas_cell
+    pub fn sprite_cells(&mut self, dst: Rect, atlas_cell: (u32, u32), cells: (u32, u32), flip: bool, rotation: f32) {
+        let uv = Rect::new(
+            atlas_cell.0 as f32 * ATLAS_CELL / ATLAS_SIZE,
+            atlas_cell.1 as f32 * ATLAS_CELL / ATLAS_SIZE,
+            cells.0 as f32 * ATLAS_CELL / ATLAS_SIZE,
+            cells.1 as f32 * ATLAS_CELL / ATLAS_SIZE,
+        );
+        self.sprite_uv(dst, uv, flip, rotation);
+    }
+
+    // any rect of the atlas in [0, 1] uv space, for art that isnt on the grid like what pack-atlas makes
+    pub fn sprite_uv(&mut self, dst: Rect, uv: Rect, flip: bool, rotation: f32) {
+        let (u0, u1) = if flip {(uv.right(), uv.left())} else {(uv.left(), uv.right())};
+        let c = dst.centroid();
+        let corner = |p: Vec2| c + (p - c).rotate(rotation);
+        let (tl, tr, bl, br) = (corner(dst.tl()), corner(dst.tr()), corner(dst.bl()), corner(dst.br()));
+        self.vertex(tl, Vec2::new(u0, uv.top()));
+        self.vertex(tr, Vec2::new(u1, uv.top()));
+        self.vertex(bl, Vec2::new(u0, uv.bot()));
+        self.vertex(bl, Vec2::new(u0, uv.bot()));
+        self.vertex(tr, Vec2::new(u1, uv.top()));
+        self.vertex(br, Vec2::new(u1, uv.bot()));
+    }
+
+    // back over the whole rect then front over the left fill of it, fill in [0, 1]
+    pub fn bar(&mut self, r: Rect, fill: f32, back: Vec4, front: Vec4) {
+        let (colour, depth) = (self.colour, self.depth);
+        self.colour = back;
+        self.rect(r);
+        self.depth = depth + 0.01;
+        self.colour = front;
+        self.rect(Rect::new(r.x, r.y, r.w * fill.clamp(0.0, 1.0), r.h));
+        self.colour = colour;
+        self.depth = depth;
+    }
+
+    // which side of pos text goes, each line is aligned on its own
+    pub fn set_text_align(&mut self, align: TextAlign) {
+        self.text_align = align;
+    }
+
+    // size is the line height, pos is the top of the first line. colour tints it like everything else
+    pub fn text(&mut self, pos: Vec2, size: f32, s: &str) {
+        let glyph_w = size * GLYPH_W / GLYPH_H;
+        let uv_clip = self.uv_clip;
+        for (i, line) in s.split('\n').enumerate() {
+            let w = KRCanvas::measure_text(size, line).x;
+            let x = match self.text_align {
+                TextAlign::Left => pos.x,
+                TextAlign::Centre => pos.x - w/2.,
+                TextAlign::Right => pos.x - w,
+            };
+            let y = pos.y + i as f32 * size;
+            for (j, c) in line.chars().enumerate() {
+                if c == ' ' {
+                    continue;
+                }
+                let c = if (FIRST_GLYPH..=LAST_GLYPH).contains(&c) {c} else {'?'};
+                let k = (c as u32 - FIRST_GLYPH as u32) as f32;
+                self.uv_clip = Rect::new((FONT_ORIGIN.x + k * GLYPH_W) / ATLAS_SIZE, FONT_ORIGIN.y / ATLAS_SIZE, GLYPH_W / ATLAS_SIZE, GLYPH_H / ATLAS_SIZE);
+                self.rect(Rect::new(x + j as f32 * glyph_w, y, glyph_w, size));
+            }
+        }
+        self.uv_clip = uv_clip;
+    }
+
+    // width of the longest line and height of all of them, same units as text()
+    pub fn measure_text(size: f32, s: &str) -> Vec2 {
+        let glyph_w = size * GLYPH_W / GLYPH_H;
+        let longest = s.split('\n').map(|line| line.chars().count()).max().unwrap_or(0);
+        Vec2::new(longest as f32 * glyph_w, s.split('\n').count() as f32 * size)
+    }
+
     pub fn bytes(self) -> Vec<u8> {
         self.buf
     }
-}
\ No newline at end of file
+}
+
+#[test]
+fn test_text() {
+    let m = KRCanvas::measure_text(0.08, "12:34");
+    assert!((m.x - 0.3).abs() < 1e-6 && (m.y - 0.08).abs() < 1e-6);
+    let m = KRCanvas::measure_text(0.08, "kills\n1");
+    assert!((m.x - 0.3).abs() < 1e-6 && (m.y - 0.16).abs() < 1e-6);
+
+    // two triangles of 3 verts each per glyph, spaces dont draw anything
+    let mut kc = KRCanvas::new(Rect::new(0.0, 0.0, 1.0, 1.0));
+    kc.set_text_align(TextAlign::Right);
+    kc.text(Vec2::new(1.0, 0.0), 0.1, "a b\nc");
+    assert_eq!(kc.bytes().len(), 3 * 6 * 9 * 4);
+}
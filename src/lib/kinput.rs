@@ -175,7 +175,7 @@ impl EventAggregator {
                 self.current.mouse_delta = self.instant_mouse_pos - self.current.mouse_pos;
                 self.current.mouse_pos = self.instant_mouse_pos;
                 let state = self.current.clone();
-                self.current.seed = khash(self.current.seed * 196513497);
+                self.current.seed = khash(self.current.seed.wrapping_mul(196513497));
                 self.current.scroll_up = false;
                 self.current.scroll_down = false;
                 self.current.keys.retain(|k, v| match v {KeyStatus::JustReleased => false, _ => true});
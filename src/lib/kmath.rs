@@ -1,4 +1,7 @@
+use std::collections::HashMap;
+
 use itertools::Itertools;
+use serde::{Serialize, Deserialize};
 
 /***************************************************
  * Easing
@@ -35,9 +38,9 @@ pub fn cubic_bezier(start: Vec2, c1: Vec2, c2: Vec2, end: Vec2, t: f32) -> Vec2
  ***************************************************/
 
 pub fn khash(mut state: u32) -> u32 {
-    state = (state ^ 2747636419) * 2654435769;
-    state = (state ^ (state >> 16)) * 2654435769;
-    state = (state ^ (state >> 16)) * 2654435769;
+    state = (state ^ 2747636419).wrapping_mul(2654435769);
+    state = (state ^ (state >> 16)).wrapping_mul(2654435769);
+    state = (state ^ (state >> 16)).wrapping_mul(2654435769);
     state
 }
 
@@ -70,7 +73,7 @@ pub fn kround(x: f32, m: f32) -> f32 {
 pub fn seed_grid(seed: u32, x: f32, y: f32, spacing: f32) -> u32 {
         let xseed = (kround(x,spacing)/spacing) as i32;
         let yseed = (kround(y,spacing)/spacing) as i32;
-        (seed as i32).wrapping_add(xseed * 1232412325).wrapping_add(yseed * 1413512387) as u32
+        (seed as i32).wrapping_add(xseed.wrapping_mul(1232412325)).wrapping_add(yseed.wrapping_mul(1413512387)) as u32
 }
 
 #[test]
@@ -134,7 +137,7 @@ fn test_round() {
  * Vec
  ***************************************************/
 
-#[derive(Copy, Clone, Debug, PartialEq)]
+#[derive(Copy, Clone, Debug, PartialEq, Serialize, Deserialize)]
 pub struct Vec2 {
     pub x: f32,
     pub y: f32,
@@ -569,6 +572,25 @@ impl Rect {
             self.h / from.h * to.h,
         )
     }
+
+    // fraction along a->b where the segment first enters the rect, slab method
+    pub fn segment_toi(&self, a: Vec2, b: Vec2) -> Option<f32> {
+        let d = b - a;
+        let mut t_enter: f32 = 0.0;
+        let mut t_exit: f32 = 1.0;
+        for (p, dp, lo, hi) in [(a.x, d.x, self.left(), self.right()), (a.y, d.y, self.top(), self.bot())] {
+            if dp == 0.0 {
+                if p < lo || p > hi {return None};
+                continue;
+            }
+            let t1 = (lo - p) / dp;
+            let t2 = (hi - p) / dp;
+            t_enter = t_enter.max(t1.min(t2));
+            t_exit = t_exit.min(t1.max(t2));
+            if t_enter > t_exit {return None};
+        }
+        Some(t_enter)
+    }
 }
 
 pub struct Triangle {
@@ -610,6 +632,105 @@ impl Triangle {
     }
 }
 
+// fraction along a->b where the segment first comes within r of center
+// if a already starts inside its 0
+pub fn segment_circle_toi(a: Vec2, b: Vec2, center: Vec2, r: f32) -> Option<f32> {
+    let d = b - a;
+    let f = a - center;
+    let c = f.dot(f) - r*r;
+    if c <= 0.0 {return Some(0.0)};
+    let aa = d.dot(d);
+    if aa == 0.0 {return None};
+    let bb = 2.0 * f.dot(d);
+    let disc = bb*bb - 4.0*aa*c;
+    if disc < 0.0 {return None};
+    let t = (-bb - disc.sqrt()) / (2.0 * aa);
+    if (0.0..=1.0).contains(&t) {
+        Some(t)
+    } else {
+        None
+    }
+}
+
+#[test]
+fn test_swept() {
+    // fast thing going straight through a small circle still hits it
+    let toi = segment_circle_toi(Vec2::new(0.0, 0.0), Vec2::new(10.0, 0.0), Vec2::new(5.0, 0.0), 0.1);
+    assert!((toi.unwrap() - 0.49).abs() < 0.0001);
+    assert_eq!(segment_circle_toi(Vec2::new(0.0, 0.0), Vec2::new(10.0, 0.0), Vec2::new(5.0, 1.0), 0.1), None);
+    assert_eq!(segment_circle_toi(Vec2::new(0.0, 0.0), Vec2::new(1.0, 0.0), Vec2::new(5.0, 0.0), 0.1), None);
+
+    let r = Rect::new(1.0, -1.0, 1.0, 2.0);
+    assert_eq!(r.segment_toi(Vec2::new(0.0, 0.0), Vec2::new(4.0, 0.0)), Some(0.25));
+    assert_eq!(r.segment_toi(Vec2::new(0.0, 2.0), Vec2::new(4.0, 2.0)), None);
+    assert_eq!(r.segment_toi(Vec2::new(0.0, 0.0), Vec2::new(0.5, 0.0)), None);
+    assert_eq!(r.segment_toi(Vec2::new(1.5, 0.0), Vec2::new(4.0, 0.0)), Some(0.0));
+}
+
+/***************************************************
+ * Spatial hash
+ ***************************************************/
+
+// Uniform grid of indices into some other array of positions. Rebuild it whenever the positions
+// move and query around a point to get candidates, still need to do the actual distance check
+pub struct SpatialHash {
+    cell_size: f32,
+    cells: HashMap<(i32, i32), Vec<usize>>,
+}
+
+impl SpatialHash {
+    pub fn new(cell_size: f32) -> SpatialHash {
+        SpatialHash {
+            cell_size,
+            cells: HashMap::new(),
+        }
+    }
+
+    pub fn build(positions: &[Vec2], cell_size: f32) -> SpatialHash {
+        let mut sh = SpatialHash::new(cell_size);
+        for (idx, p) in positions.iter().enumerate() {
+            sh.insert(*p, idx);
+        }
+        sh
+    }
+
+    fn cell(&self, p: Vec2) -> (i32, i32) {
+        ((p.x / self.cell_size).floor() as i32, (p.y / self.cell_size).floor() as i32)
+    }
+
+    pub fn insert(&mut self, p: Vec2, idx: usize) {
+        let cell = self.cell(p);
+        self.cells.entry(cell).or_default().push(idx);
+    }
+
+    // everything in cells touching the square around p, in a deterministic order
+    pub fn query(&self, p: Vec2, radius: f32, out: &mut Vec<usize>) {
+        out.clear();
+        let (x0, y0) = self.cell(p - Vec2::new(radius, radius));
+        let (x1, y1) = self.cell(p + Vec2::new(radius, radius));
+        for j in y0..=y1 {
+            for i in x0..=x1 {
+                if let Some(idxs) = self.cells.get(&(i, j)) {
+                    out.extend_from_slice(idxs);
+                }
+            }
+        }
+    }
+}
+
+#[test]
+fn test_spatial_hash() {
+    let positions: Vec<Vec2> = (0..1000).map(|i| Vec2::new(kuniform(i, -5.0, 5.0), kuniform(i + 10000, -5.0, 5.0))).collect();
+    let sh = SpatialHash::build(&positions, 0.3);
+    let mut out = Vec::new();
+    for (p, r) in [(Vec2::new(0.0, 0.0), 0.5), (Vec2::new(-4.9, 3.0), 1.0), (Vec2::new(2.0, -2.0), 0.05)] {
+        sh.query(p, r, &mut out);
+        let mut found: Vec<usize> = out.iter().copied().filter(|idx| positions[*idx].dist(p) < r).collect();
+        found.sort();
+        let brute: Vec<usize> = (0..positions.len()).filter(|idx| positions[*idx].dist(p) < r).collect();
+        assert_eq!(found, brute);
+    }
+}
 
 #[test]
 fn test_floor() {
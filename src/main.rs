@@ -1,8 +1,15 @@
 mod lib;
 mod application;
 mod krenderer;
+mod sprites;
 mod kaudio;
 mod game;
+mod simulation;
+mod flowfield;
+mod replay;
+mod save;
+mod profile;
+mod bench;
 
 use application::*;
 use glutin::event::{Event, WindowEvent};
@@ -12,8 +19,22 @@ use std::env;
 fn main() {
     env::set_var("RUST_BACKTRACE", "1");
 
+    // --record <file> saves every frame of input, --replay <file> plays it back headless
+    // --bench times the simulation with lots of enemies
+    let args: Vec<String> = env::args().collect();
+    let arg_value = |flag: &str| args.iter().position(|a| a == flag).and_then(|i| args.get(i + 1)).cloned();
+
+    if let Some(path) = arg_value("--replay") {
+        replay::run_headless(&path);
+        return;
+    }
+    if args.iter().any(|a| a == "--bench") {
+        bench::run_benchmark();
+        return;
+    }
+
     let event_loop = glutin::event_loop::EventLoop::new();
-    let mut application = Application::new(&event_loop);
+    let mut application = Application::new(&event_loop, arg_value("--record"));
     
     event_loop.run(move |event, _, control_flow| {
         application.handle_event(&event);
@@ -0,0 +1,173 @@
+use crate::simulation::*;
+
+use serde::{Serialize, Deserialize};
+
+// The bit that carries over between runs: some of the scrap from each run gets banked
+// and spent on upgrades that every run after starts with. Lives next to the quicksave.
+
+pub const PROFILE_VERSION: u64 = 1;
+
+pub const PROFILE_PATH: &str = "profile.json";
+
+// fraction of the scrap collected in a run that gets banked when it ends
+pub const BANK_FRACTION: f32 = 0.25;
+
+// how many runs to remember having banked, quickloading back into one of them cant bank it again
+pub const BANKED_RUNS_KEPT: usize = 32;
+
+pub struct MetaUpgradeDef {
+    pub max_level: i32,
+    pub base_cost: i32,
+    pub cost_per_level: i32,    // each level costs this much more than the last
+    pub requires: Option<usize>,    // has to have at least one level of this first
+}
+
+pub const META_START_HP: usize = 0;
+pub const META_PICKUP_RADIUS: usize = 1;
+pub const META_TURRET: usize = 2;
+pub const META_GENERATOR: usize = 3;
+
+// indexed by meta upgrade, only append to it or old profiles will get the wrong levels
+pub const META_UPGRADES: [MetaUpgradeDef; 4] = [
+    MetaUpgradeDef { max_level: 3, base_cost: 10, cost_per_level: 10, requires: None },
+    MetaUpgradeDef { max_level: 3, base_cost: 8, cost_per_level: 8, requires: None },
+    MetaUpgradeDef { max_level: 1, base_cost: 15, cost_per_level: 0, requires: None },
+    MetaUpgradeDef { max_level: 1, base_cost: 25, cost_per_level: 0, requires: Some(META_TURRET) },
+];
+
+pub const META_HP_PER_LEVEL: f32 = 0.25;
+pub const META_PICKUP_RADIUS_PER_LEVEL: f32 = 0.1;
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct Profile {
+    pub version: u64,
+    pub banked_scrap: i32,
+    pub levels: Vec<i32>,   // indexed by meta upgrade
+    #[serde(default)]
+    pub banked_runs: Vec<u32>,  // world seeds, newest last
+}
+
+impl Profile {
+    pub fn new() -> Profile {
+        Profile {
+            version: PROFILE_VERSION,
+            banked_scrap: 0,
+            levels: vec![0; META_UPGRADES.len()],
+            banked_runs: Vec::new(),
+        }
+    }
+
+    pub fn level(&self, upgrade: usize) -> i32 {
+        self.levels.get(upgrade).copied().unwrap_or(0)
+    }
+
+    // None if its maxed out
+    pub fn cost(&self, upgrade: usize) -> Option<i32> {
+        let def = &META_UPGRADES[upgrade];
+        let level = self.level(upgrade);
+        if level >= def.max_level {
+            return None;
+        }
+        Some(def.base_cost + def.cost_per_level * level)
+    }
+
+    pub fn available(&self, upgrade: usize) -> bool {
+        META_UPGRADES[upgrade].requires.is_none_or(|r| self.level(r) > 0)
+    }
+
+    pub fn can_buy(&self, upgrade: usize) -> bool {
+        self.available(upgrade) && self.cost(upgrade).is_some_and(|c| c <= self.banked_scrap)
+    }
+
+    pub fn buy(&mut self, upgrade: usize) -> bool {
+        if !self.can_buy(upgrade) {
+            return false;
+        }
+        self.banked_scrap -= self.cost(upgrade).unwrap();
+        self.levels[upgrade] += 1;
+        true
+    }
+
+    // false if this run has already been banked
+    pub fn bank(&mut self, sim: &Simulation) -> bool {
+        if self.banked_runs.contains(&sim.world_seed) {
+            return false;
+        }
+        self.banked_runs.push(sim.world_seed);
+        if self.banked_runs.len() > BANKED_RUNS_KEPT {
+            self.banked_runs.remove(0);
+        }
+        self.banked_scrap += (sim.scrap_collected as f32 * BANK_FRACTION) as i32;
+        true
+    }
+
+    // set up a fresh run with everything thats been bought
+    pub fn apply(&self, sim: &mut Simulation) {
+        sim.player_stats.max_hp += META_HP_PER_LEVEL * self.level(META_START_HP) as f32;
+        sim.player_hp = sim.player_stats.max_hp;
+        sim.player_stats.pickup_radius += META_PICKUP_RADIUS_PER_LEVEL * self.level(META_PICKUP_RADIUS) as f32;
+        sim.unlocked_buildings = vec![BUILDING_WALL];
+        if self.level(META_TURRET) > 0 {
+            sim.unlocked_buildings.push(BUILDING_TURRET);
+        }
+        if self.level(META_GENERATOR) > 0 {
+            sim.unlocked_buildings.push(BUILDING_GENERATOR);
+        }
+    }
+}
+
+pub fn save_profile(path: &str, profile: &Profile) -> Result<(), String> {
+    let s = serde_json::to_string(profile).map_err(|e| e.to_string())?;
+    std::fs::write(path, s).map_err(|e| format!("couldn't write {}: {}", path, e))
+}
+
+// a missing profile is just a new player
+pub fn load_profile(path: &str) -> Result<Profile, String> {
+    let s = match std::fs::read_to_string(path) {
+        Ok(s) => s,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(Profile::new()),
+        Err(e) => return Err(format!("couldn't read {}: {}", path, e)),
+    };
+    let mut profile: Profile = serde_json::from_str(&s).map_err(|e| e.to_string())?;
+    if profile.version > PROFILE_VERSION {
+        return Err(format!("profile version {} is newer than this build ({})", profile.version, PROFILE_VERSION));
+    }
+    // upgrades added since it was saved
+    profile.levels.resize(META_UPGRADES.len().max(profile.levels.len()), 0);
+    profile.version = PROFILE_VERSION;
+    Ok(profile)
+}
+
+#[test]
+fn test_profile() {
+    let mut profile = Profile::new();
+    let mut sim = Simulation::new(1);
+    sim.scrap_collected = 100;
+    assert!(profile.bank(&sim));
+    assert_eq!(profile.banked_scrap, 25);
+    // same run again, like after quickloading back into it
+    assert!(!profile.bank(&sim));
+    assert_eq!(profile.banked_scrap, 25);
+
+    // generator needs the turret first
+    assert!(!profile.buy(META_GENERATOR));
+    assert!(profile.buy(META_TURRET));
+    assert!(!profile.buy(META_TURRET));
+    assert_eq!(profile.banked_scrap, 10);
+    assert!(profile.buy(META_START_HP));
+    assert!(!profile.buy(META_START_HP));
+
+    let mut sim = Simulation::new(1);
+    profile.apply(&mut sim);
+    assert_eq!(sim.player_hp, 1.0 + META_HP_PER_LEVEL);
+    assert_eq!(sim.unlocked_buildings, vec![BUILDING_WALL, BUILDING_TURRET]);
+    sim.player_scrap = 100;
+    assert!(!sim.can_place_building((5, 5), BUILDING_GENERATOR));
+    assert!(sim.can_place_building((5, 5), BUILDING_TURRET));
+
+    let path = std::env::temp_dir().join("scrap_survivor_test_profile.json");
+    let path = path.to_str().unwrap();
+    save_profile(path, &profile).unwrap();
+    assert_eq!(load_profile(path).unwrap(), profile);
+    std::fs::remove_file(path).unwrap();
+}
@@ -0,0 +1,284 @@
+use std::collections::HashMap;
+use std::fs::File;
+use std::io::{BufReader, BufWriter, Read, Write, Result, Error, ErrorKind};
+
+use crate::lib::kinput::*;
+use crate::lib::kmath::*;
+use crate::game::*;
+use crate::profile::*;
+
+use glutin::event::VirtualKeyCode;
+
+// Replay file is just every FrameInputState the game consumed, packed little endian:
+//  header: b"SSRP", version u8, profile_len u32, profile_len bytes of profile json (0 for none)
+//  frame: dt f64, seed u32, screen_rect 4xf32, mouse_pos 2xf32, lmb rmb mmb u8, scroll u8, n_keys u8, n_keys x (key u8, status u8)
+// Everything the sim sees is derived from these so playing them back through Game::update reproduces the run exactly.
+// The profile is whatever it was when recording started, upgrades bought during the run are in the clicks.
+// Version 1 had no profile.
+// F9 gets recorded so a quickload shows up, but the save it loaded isnt in the file so those replays get rejected.
+// A run that crashed or got killed usually stops partway through a frame, that frame is dropped and the rest plays.
+
+const MAGIC: &[u8; 4] = b"SSRP";
+const VERSION: u8 = 2;
+
+// seconds of game time between flushes, so a run that gets killed still leaves most of itself behind
+const FLUSH_INTERVAL: f64 = 1.0;
+
+// keys are stored as an index into this so only append to it
+const RECORDED_KEYS: [VirtualKeyCode; 15] = [
+    VirtualKeyCode::W,
+    VirtualKeyCode::A,
+    VirtualKeyCode::S,
+    VirtualKeyCode::D,
+    VirtualKeyCode::Q,
+    VirtualKeyCode::E,
+    VirtualKeyCode::R,
+    VirtualKeyCode::LShift,
+    VirtualKeyCode::Key1,
+    VirtualKeyCode::Key2,
+    VirtualKeyCode::Key3,
+    VirtualKeyCode::Key4,
+    VirtualKeyCode::Key5,
+    VirtualKeyCode::T,
+    VirtualKeyCode::F9,
+];
+
+fn key_status_to_u8(ks: KeyStatus) -> u8 {
+    match ks {
+        KeyStatus::Pressed => 0,
+        KeyStatus::JustPressed => 1,
+        KeyStatus::JustReleased => 2,
+        KeyStatus::Released => 3,
+    }
+}
+
+fn key_status_from_u8(b: u8) -> Result<KeyStatus> {
+    match b {
+        0 => Ok(KeyStatus::Pressed),
+        1 => Ok(KeyStatus::JustPressed),
+        2 => Ok(KeyStatus::JustReleased),
+        3 => Ok(KeyStatus::Released),
+        _ => Err(Error::new(ErrorKind::InvalidData, "bad key status")),
+    }
+}
+
+pub struct ReplayWriter {
+    out: BufWriter<File>,
+    last_flush_t: f64,
+}
+
+impl ReplayWriter {
+    pub fn new(path: &str, profile: Option<&Profile>) -> Result<ReplayWriter> {
+        let mut out = BufWriter::new(File::create(path)?);
+        out.write_all(MAGIC)?;
+        out.write_all(&[VERSION])?;
+        let profile = match profile {
+            Some(p) => serde_json::to_vec(p).map_err(Error::other)?,
+            None => Vec::new(),
+        };
+        out.write_all(&(profile.len() as u32).to_le_bytes())?;
+        out.write_all(&profile)?;
+        Ok(ReplayWriter { out, last_flush_t: 0.0 })
+    }
+
+    pub fn record(&mut self, inputs: &FrameInputState) -> Result<()> {
+        let mut buf = Vec::with_capacity(64);
+        buf.extend_from_slice(&inputs.dt.to_le_bytes());
+        buf.extend_from_slice(&inputs.seed.to_le_bytes());
+        for x in [inputs.screen_rect.x, inputs.screen_rect.y, inputs.screen_rect.w, inputs.screen_rect.h, inputs.mouse_pos.x, inputs.mouse_pos.y] {
+            buf.extend_from_slice(&x.to_le_bytes());
+        }
+        buf.push(key_status_to_u8(inputs.lmb));
+        buf.push(key_status_to_u8(inputs.rmb));
+        buf.push(key_status_to_u8(inputs.mmb));
+        buf.push(inputs.scroll_up as u8 | (inputs.scroll_down as u8) << 1);
+
+        let keys: Vec<(u8, u8)> = RECORDED_KEYS.iter().enumerate()
+            .filter_map(|(i, k)| inputs.keys.get(k).map(|ks| (i as u8, key_status_to_u8(*ks))))
+            .collect();
+        buf.push(keys.len() as u8);
+        for (k, ks) in keys {
+            buf.push(k);
+            buf.push(ks);
+        }
+        self.out.write_all(&buf)?;
+        if inputs.t - self.last_flush_t >= FLUSH_INTERVAL {
+            self.last_flush_t = inputs.t;
+            self.out.flush()?;
+        }
+        Ok(())
+    }
+
+    pub fn flush(&mut self) -> Result<()> {
+        self.out.flush()
+    }
+}
+
+// t and frame are left for the caller, theyre running totals
+fn read_frame<'a>(take: &mut impl FnMut(usize) -> Result<&'a [u8]>) -> Result<FrameInputState> {
+    let dt = f64::from_le_bytes(take(8)?.try_into().unwrap());
+    let seed = u32::from_le_bytes(take(4)?.try_into().unwrap());
+    let mut fs = [0.0f32; 6];
+    for f in fs.iter_mut() {
+        *f = f32::from_le_bytes(take(4)?.try_into().unwrap());
+    }
+    let buttons = take(4)?;
+    let (lmb, rmb, mmb, scroll) = (key_status_from_u8(buttons[0])?, key_status_from_u8(buttons[1])?, key_status_from_u8(buttons[2])?, buttons[3]);
+    let n_keys = take(1)?[0];
+    let mut keys = HashMap::new();
+    for _ in 0..n_keys {
+        let kb = take(2)?;
+        let k = *RECORDED_KEYS.get(kb[0] as usize).ok_or(Error::new(ErrorKind::InvalidData, "bad key"))?;
+        keys.insert(k, key_status_from_u8(kb[1])?);
+    }
+    Ok(FrameInputState {
+        screen_rect: Rect::new(fs[0], fs[1], fs[2], fs[3]),
+        mouse_pos: Vec2::new(fs[4], fs[5]),
+        mouse_delta: Vec2::new(0.0, 0.0),
+        keys,
+        lmb,
+        rmb,
+        mmb,
+        scroll_up: scroll & 1 != 0,
+        scroll_down: scroll & 2 != 0,
+        t: 0.0,
+        dt,
+        frame: 0,
+        seed,
+    })
+}
+
+pub fn read_replay(path: &str) -> Result<(Option<Profile>, Vec<FrameInputState>)> {
+    let mut bytes = Vec::new();
+    BufReader::new(File::open(path)?).read_to_end(&mut bytes)?;
+
+    let eof = || Error::new(ErrorKind::UnexpectedEof, "replay truncated");
+    let mut idx = 0;
+    let mut take = |n: usize| -> Result<&[u8]> {
+        if idx + n > bytes.len() {
+            return Err(eof());
+        }
+        idx += n;
+        Ok(&bytes[idx-n..idx])
+    };
+
+    if take(4)? != MAGIC {
+        return Err(Error::new(ErrorKind::InvalidData, "not a replay file"));
+    }
+    let version = take(1)?[0];
+    if version == 0 || version > VERSION {
+        return Err(Error::new(ErrorKind::InvalidData, "unsupported replay version"));
+    }
+    let mut profile = None;
+    if version >= 2 {
+        let len = u32::from_le_bytes(take(4)?.try_into().unwrap()) as usize;
+        if len > 0 {
+            profile = Some(serde_json::from_slice(take(len)?).map_err(|e| Error::new(ErrorKind::InvalidData, e))?);
+        }
+    }
+
+    let mut frames = Vec::new();
+    let mut t = 0.0;
+    loop {
+        let mut frame = match read_frame(&mut take) {
+            Ok(frame) => frame,
+            // clean end or partway through the last one
+            Err(e) if e.kind() == ErrorKind::UnexpectedEof => break,
+            Err(e) => return Err(e),
+        };
+        if frame.keys.get(&VirtualKeyCode::F9) == Some(&KeyStatus::JustPressed) {
+            return Err(Error::new(ErrorKind::InvalidData, format!("quickload on frame {}, cant reproduce the run from inputs alone", frames.len())));
+        }
+        t += frame.dt;
+        frame.t = t;
+        frame.frame = frames.len() as u32 + 1;
+        frames.push(frame);
+    }
+
+    Ok((profile, frames))
+}
+
+// Play a replay back with no window and print state hashes along the way, so two runs can be diffed
+pub fn run_headless(path: &str) {
+    let (profile, frames) = read_replay(path).unwrap_or_else(|e| panic!("couldn't read replay {}: {}", path, e));
+    // never write the profile back, its not our run
+    let mut game = Game::new(profile, None);
+    for (i, inputs) in frames.iter().enumerate() {
+        game.update(inputs);
+        if i % 600 == 0 {
+            println!("frame {} hash {:016x}", i, game.sim().state_hash());
+        }
+    }
+    let sim = game.sim();
+    println!("replayed {} frames, t = {:.3}", frames.len(), sim.t);
+    println!("player pos {:?} hp {} scrap {}", sim.player_pos, sim.player_hp, sim.player_scrap);
+    println!("{} enemies {} scrap {} buildings", sim.enemy_pos.len(), sim.scrap_pos.len(), sim.building_pos.len());
+    println!("final hash {:016x}", sim.state_hash());
+}
+
+#[test]
+fn test_replay_roundtrip() {
+    let mut frames = Vec::new();
+    let mut seed = 1234;
+    for i in 0..240 {
+        let mut keys = HashMap::new();
+        keys.insert(if i < 120 {VirtualKeyCode::D} else {VirtualKeyCode::W}, KeyStatus::Pressed);
+        frames.push(FrameInputState {
+            screen_rect: Rect::new(0.0, 0.0, 1.0, 1.0),
+            mouse_pos: Vec2::new(0.7, 0.5),
+            mouse_delta: Vec2::new(0.0, 0.0),
+            keys,
+            lmb: if i % 30 == 0 {KeyStatus::Pressed} else {KeyStatus::Released},
+            rmb: KeyStatus::Released,
+            mmb: KeyStatus::Released,
+            scroll_up: false,
+            scroll_down: false,
+            t: 0.0,
+            dt: 0.01 + (i % 3) as f64 * 0.005,
+            frame: i,
+            seed,
+        });
+        seed = khash(seed);
+    }
+
+    let path = std::env::temp_dir().join("scrap_survivor_test.replay");
+    let path = path.to_str().unwrap();
+    let mut profile = Profile::new();
+    profile.banked_scrap = 7;
+    profile.levels[META_START_HP] = 1;
+    let mut writer = ReplayWriter::new(path, Some(&profile)).unwrap();
+    for f in frames.iter() {
+        writer.record(f).unwrap();
+    }
+    writer.flush().unwrap();
+
+    let (read_profile, read) = read_replay(path).unwrap();
+    assert_eq!(read.len(), frames.len());
+    assert_eq!(read_profile.as_ref(), Some(&profile));
+
+    let mut a = Game::new(Some(profile.clone()), None);
+    let mut b = Game::new(read_profile, None);
+    for (fa, fb) in frames.iter().zip(read.iter()) {
+        a.update(fa);
+        b.update(fb);
+    }
+    assert_eq!(a.sim().state_hash(), b.sim().state_hash());
+
+    // killed partway through writing a frame, the ones before it still play
+    let bytes = std::fs::read(path).unwrap();
+    std::fs::write(path, &bytes[..bytes.len() - 3]).unwrap();
+    let (read_profile, read) = read_replay(path).unwrap();
+    assert_eq!(read.len(), frames.len() - 1);
+    assert_eq!(read_profile.as_ref(), Some(&profile));
+    assert!(read[100].keys == frames[100].keys);
+
+    // a quickload in the middle means it cant be played back
+    let mut writer = ReplayWriter::new(path, None).unwrap();
+    frames[100].keys.insert(VirtualKeyCode::F9, KeyStatus::JustPressed);
+    for f in frames.iter() {
+        writer.record(f).unwrap();
+    }
+    writer.flush().unwrap();
+    assert!(read_replay(path).is_err());
+    std::fs::remove_file(path).unwrap();
+}
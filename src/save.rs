@@ -0,0 +1,155 @@
+use crate::simulation::*;
+
+use serde_json::{json, Value};
+
+// Bump this whenever Simulation changes shape and add a step to migrate() that brings the
+// previous version up to date, so old saves keep loading.
+pub const SAVE_VERSION: u64 = 10;
+
+pub const QUICKSAVE_PATH: &str = "quicksave.json";
+
+pub fn save_game(path: &str, zoom_level: f32, sim: &Simulation) -> Result<(), String> {
+    let save = json!({
+        "version": SAVE_VERSION,
+        "zoom_level": zoom_level,
+        "sim": sim,
+    });
+    let s = serde_json::to_string(&save).map_err(|e| e.to_string())?;
+    std::fs::write(path, s).map_err(|e| format!("couldn't write {}: {}", path, e))
+}
+
+pub fn load_game(path: &str) -> Result<(f32, Simulation), String> {
+    let s = std::fs::read_to_string(path).map_err(|e| format!("couldn't read {}: {}", path, e))?;
+    let save: Value = serde_json::from_str(&s).map_err(|e| e.to_string())?;
+    let mut save = migrate(save)?;
+
+    let zoom_level = save["zoom_level"].as_f64().ok_or("save has no zoom_level")? as f32;
+    let mut sim: Simulation = serde_json::from_value(save["sim"].take()).map_err(|e| e.to_string())?;
+    sim.rebuild_building_grid();
+    Ok((zoom_level, sim))
+}
+
+// one step per old version, each taking it to the next version
+fn migrate(mut save: Value) -> Result<Value, String> {
+    let version = save["version"].as_u64().ok_or("save has no version")?;
+    if version > SAVE_VERSION {
+        return Err(format!("save version {} is newer than this build ({})", version, SAVE_VERSION));
+    }
+    for from in version..SAVE_VERSION {
+        match from {
+            1 => {
+                // projectiles got lifetimes and pierce
+                let n = save["sim"]["projectile_pos"].as_array().map_or(0, |a| a.len());
+                let t = save["sim"]["t"].clone();
+                save["sim"]["projectile_end_t"] = json!(vec![t; n]);
+                save["sim"]["projectile_pierce"] = json!(vec![0; n]);
+            },
+            2 => {
+                // enemies got kinds, everything before was a grunt
+                let n = save["sim"]["enemy_pos"].as_array().map_or(0, |a| a.len());
+                save["sim"]["enemy_kind"] = json!(vec![ENEMY_GRUNT; n]);
+                save["sim"]["enemy_next_t"] = json!(vec![0.0; n]);
+            },
+            3 => {
+                // nobody has died yet as far as the ghosts know
+                save["sim"]["dead_pos"] = json!([]);
+            },
+            4 => {
+                // the player can get hurt now, and we keep score
+                save["sim"]["player_hit_t"] = json!(0.0);
+                save["sim"]["kills"] = json!(0);
+                save["sim"]["scrap_collected"] = save["sim"]["player_scrap"].clone();
+            },
+            5 => {
+                // enemies got a knockback velocity
+                let n = save["sim"]["enemy_pos"].as_array().map_or(0, |a| a.len());
+                save["sim"]["enemy_knock"] = json!(vec![json!({"x": 0.0, "y": 0.0}); n]);
+            },
+            6 => {
+                // more weapons than just melee, they start full
+                save["sim"]["player_ammo"] = json!(WEAPON_DEFS.iter().map(|w| w.clip).collect::<Vec<_>>());
+            },
+            7 => {
+                // cooldowns went per weapon, and survivor mode with its levels and upgrades came in
+                let next_t = save["sim"]["player_next_t"].take();
+                save["sim"]["player_weapon_next_t"] = json!(vec![next_t; WEAPON_DEFS.len()]);
+                save["sim"]["player_attack_dir"] = json!({"x": 1.0, "y": 0.0});
+                save["sim"]["player_attack_weapon"] = json!(WEAPON_MELEE);
+                save["sim"]["player_stats"] = json!(PlayerStats::default());
+                save["sim"]["survivor_mode"] = json!(false);
+                save["sim"]["player_weapons"] = json!([WEAPON_MELEE]);
+                save["sim"]["player_xp"] = json!(0);
+                save["sim"]["player_level"] = json!(0);
+                save["sim"]["level_up_choices"] = json!([]);
+                let damage = save["sim"]["projectile_kind"].as_array().map_or(Ok(Vec::new()), |a| a.iter()
+                    .map(|k| k.as_u64().and_then(|k| PROJECTILE_DEFS.get(k as usize)).map(|def| def.damage)
+                        .ok_or(format!("save has a bad projectile kind {}", k)))
+                    .collect::<Result<Vec<f32>, String>>())?;
+                save["sim"]["projectile_damage"] = json!(damage);
+            },
+            8 => {
+                // max hp and unlocking buildings came in with the profile
+                save["sim"]["player_stats"]["max_hp"] = json!(1.0);
+                save["sim"]["unlocked_buildings"] = json!([BUILDING_WALL, BUILDING_TURRET, BUILDING_GENERATOR]);
+            },
+            9 => {
+                // enemies remember noticing the player, nobody has yet
+                let n = save["sim"]["enemy_pos"].as_array().map_or(0, |a| a.len());
+                save["sim"]["enemy_chasing"] = json!(vec![false; n]);
+            },
+            _ => return Err(format!("don't know how to migrate save version {}", from)),
+        }
+    }
+    save["version"] = json!(SAVE_VERSION);
+    Ok(save)
+}
+
+#[test]
+fn test_save_roundtrip() {
+    let mut sim = Simulation::new(420);
+    let mut commands = PlayerCommands::idle();
+    commands.steer = crate::lib::kmath::Vec2::new(0.0, 1.0);
+    for _ in 0..60 {
+        sim.step(&commands, SIM_DT);
+    }
+    // put a wall down the way the player would
+    sim.player_scrap = 100;
+    commands.attack = true;
+    commands.place_building = Some(BUILDING_WALL);
+    commands.aim = sim.player_pos + crate::lib::kmath::Vec2::new(0.5, 0.0);
+    let cell = building_cell(commands.aim);
+    sim.step(&commands, SIM_DT);
+    assert_eq!(sim.building_at(cell), Some(0));
+    commands.attack = false;
+    commands.place_building = None;
+
+    let path = std::env::temp_dir().join("scrap_survivor_test_save.json");
+    let path = path.to_str().unwrap();
+    save_game(path, 2.0, &sim).unwrap();
+    let (zoom_level, mut loaded) = load_game(path).unwrap();
+    std::fs::remove_file(path).unwrap();
+
+    assert_eq!(zoom_level, 2.0);
+    assert_eq!(loaded.state_hash(), sim.state_hash());
+
+    // the grid isnt saved, it comes back from the building list
+    assert_eq!(loaded.building_grid, sim.building_grid);
+    assert_eq!(loaded.building_at(cell), Some(0));
+    assert!(loaded.placement_blocked(cell));
+    assert!(!loaded.can_place_building(cell, BUILDING_WALL));
+
+    // and it keeps going the same way
+    for _ in 0..60 {
+        sim.step(&commands, SIM_DT);
+        loaded.step(&commands, SIM_DT);
+    }
+    assert_eq!(loaded.state_hash(), sim.state_hash());
+}
+
+#[test]
+fn test_bad_save() {
+    // a corrupt projectile kind fails the load instead of panicking
+    let save = json!({"version": 7, "zoom_level": 1.0, "sim": {"projectile_kind": [PROJECTILE_BULLET, 99]}});
+    assert!(migrate(save).is_err());
+    assert!(migrate(json!({"version": SAVE_VERSION + 1})).is_err());
+}
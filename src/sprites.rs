@@ -0,0 +1,172 @@
+use std::collections::HashMap;
+
+use crate::lib::kmath::*;
+use crate::krenderer::*;
+
+use serde::{Serialize, Deserialize};
+
+// Names for the art in the atlas so drawing code doesnt hardcode cells, loaded from atlas.json:
+//  { "sprites": { "explosion": { "cell": [6, 0] }, "book": { "cell": [0, 1], "cells": [2, 1] } } }
+// or by uv rect [x, y, w, h] in [0, 1] instead of a cell, which is what pack-atlas writes:
+//  { "sprites": { "explosion": { "uv": [0.5, 0.0, 0.125, 0.125] } } }
+// Anything not in there gets drawn as flat shapes like before.
+// Animations go in there too, a run of cells each shown for a duration in seconds:
+//  { "animations": { "scrap_bob": { "frames": [{ "cell": [1, 0], "duration": 0.2 }, ...], "looping": true } } }
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct SpriteDef {
+    #[serde(default)]
+    pub cell: (u32, u32),
+    #[serde(default = "one_cell")]
+    pub cells: (u32, u32),
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub uv: Option<[f32; 4]>,   // wins over cell
+}
+
+fn one_cell() -> (u32, u32) {
+    (1, 1)
+}
+
+fn yes() -> bool {
+    true
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct AnimationFrame {
+    #[serde(default)]
+    pub cell: (u32, u32),
+    #[serde(default = "one_cell")]
+    pub cells: (u32, u32),
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub uv: Option<[f32; 4]>,
+    pub duration: f32,
+}
+
+fn draw_cell_or_uv(kc: &mut KRCanvas, cell: (u32, u32), cells: (u32, u32), uv: Option<[f32; 4]>, dst: Rect, flip: bool, rotation: f32) {
+    match uv {
+        Some([x, y, w, h]) => kc.sprite_uv(dst, Rect::new(x, y, w, h), flip, rotation),
+        None if cells == (1, 1) => kc.sprite(dst, cell, flip, rotation),
+        None => kc.sprite_cells(dst, cell, cells, flip, rotation),
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct Animation {
+    pub frames: Vec<AnimationFrame>,
+    #[serde(default = "yes")]
+    pub looping: bool,  // otherwise it stops on the last frame
+}
+
+impl Animation {
+    pub fn duration(&self) -> f32 {
+        self.frames.iter().map(|f| f.duration).sum()
+    }
+
+    // t is time since it started. looping ones wrap, so negative t counts back from the end,
+    // and one shot ones clamp to the start before it and hold the last frame after
+    pub fn frame_at(&self, t: f32) -> Option<&AnimationFrame> {
+        let duration = self.duration();
+        if duration <= 0.0 {
+            return self.frames.first();
+        }
+        let mut t = if self.looping {t.rem_euclid(duration)} else {t.max(0.0)};
+        for frame in self.frames.iter() {
+            if t < frame.duration {
+                return Some(frame);
+            }
+            t -= frame.duration;
+        }
+        self.frames.last()
+    }
+}
+
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+pub struct SpriteSheet {
+    pub sprites: HashMap<String, SpriteDef>,
+    #[serde(default)]
+    pub animations: HashMap<String, Animation>,
+}
+
+impl SpriteSheet {
+    pub fn from_json(s: &str) -> Result<SpriteSheet, String> {
+        serde_json::from_str(s).map_err(|e| e.to_string())
+    }
+
+    pub fn get(&self, name: &str) -> Option<&SpriteDef> {
+        self.sprites.get(name)
+    }
+
+    // false if theres no sprite called that so the caller can draw something else
+    pub fn draw(&self, kc: &mut KRCanvas, name: &str, dst: Rect, flip: bool, rotation: f32) -> bool {
+        match self.get(name) {
+            Some(def) => {
+                draw_cell_or_uv(kc, def.cell, def.cells, def.uv, dst, flip, rotation);
+                true
+            },
+            None => false,
+        }
+    }
+
+    pub fn animation(&self, name: &str) -> Option<&Animation> {
+        self.animations.get(name)
+    }
+
+    // whichever frame is up at t, false if theres no animation called that
+    pub fn draw_animation(&self, kc: &mut KRCanvas, name: &str, t: f32, dst: Rect, flip: bool, rotation: f32) -> bool {
+        match self.animation(name).and_then(|a| a.frame_at(t)) {
+            Some(frame) => {
+                draw_cell_or_uv(kc, frame.cell, frame.cells, frame.uv, dst, flip, rotation);
+                true
+            },
+            None => false,
+        }
+    }
+
+    // for ones that show how far along something is rather than time, like damage, progress in [0, 1].
+    // these shouldnt loop or 1 wraps back to the first frame
+    pub fn draw_animation_progress(&self, kc: &mut KRCanvas, name: &str, progress: f32, dst: Rect, flip: bool, rotation: f32) -> bool {
+        match self.animation(name) {
+            Some(a) => self.draw_animation(kc, name, progress.clamp(0.0, 1.0) * a.duration(), dst, flip, rotation),
+            None => false,
+        }
+    }
+}
+
+#[test]
+fn test_sprite_sheet() {
+    let sheet = SpriteSheet::from_json(r#"{ "sprites": {
+        "explosion": { "cell": [6, 0] },
+        "book": { "cell": [0, 1], "cells": [2, 1] },
+        "packed": { "uv": [0.25, 0.5, 0.125, 0.0625] }
+    } }"#).unwrap();
+    assert_eq!(sheet.get("explosion"), Some(&SpriteDef { cell: (6, 0), cells: (1, 1), uv: None }));
+    assert_eq!(sheet.get("book").unwrap().cells, (2, 1));
+    assert_eq!(sheet.get("packed").unwrap().uv, Some([0.25, 0.5, 0.125, 0.0625]));
+
+    let mut kc = KRCanvas::new(Rect::new(0.0, 0.0, 1.0, 1.0));
+    assert!(sheet.draw(&mut kc, "explosion", Rect::new(0.0, 0.0, 0.1, 0.1), true, 1.0));
+    assert!(!sheet.draw(&mut kc, "player", Rect::new(0.0, 0.0, 0.1, 0.1), false, 0.0));
+    assert_eq!(kc.bytes().len(), 6 * 9 * 4);
+}
+
+#[test]
+fn test_animation() {
+    let sheet = SpriteSheet::from_json(r#"{ "sprites": {}, "animations": {
+        "walk": { "frames": [{ "cell": [0, 0], "duration": 0.1 }, { "cell": [1, 0], "duration": 0.3 }] },
+        "flash": { "frames": [{ "cell": [2, 0], "duration": 0.05 }, { "cell": [3, 0], "duration": 0.05 }], "looping": false }
+    } }"#).unwrap();
+    let walk = sheet.animation("walk").unwrap();
+    assert_eq!(walk.frame_at(0.05).unwrap().cell, (0, 0));
+    assert_eq!(walk.frame_at(0.2).unwrap().cell, (1, 0));
+    assert_eq!(walk.frame_at(0.45).unwrap().cell, (0, 0));
+    assert_eq!(walk.frame_at(-0.05).unwrap().cell, (1, 0));
+
+    // doesnt loop so it holds the last frame
+    let flash = sheet.animation("flash").unwrap();
+    assert_eq!(flash.frame_at(0.07).unwrap().cell, (3, 0));
+    assert_eq!(flash.frame_at(10.0).unwrap().cell, (3, 0));
+
+    let mut kc = KRCanvas::new(Rect::new(0.0, 0.0, 1.0, 1.0));
+    assert!(sheet.draw_animation_progress(&mut kc, "flash", 1.0, Rect::new(0.0, 0.0, 0.1, 0.1), false, 0.0));
+    assert!(!sheet.draw_animation(&mut kc, "run", 0.0, Rect::new(0.0, 0.0, 0.1, 0.1), false, 0.0));
+}